@@ -0,0 +1,114 @@
+// Generates manifest of vendored archives, which `vendored`
+// module verifies embedded bytes against before unpacking.
+// Names, versions and digests come from vendored/manifest.toml,
+// written by vendored.nix - every archive has to match it
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+const VENDORED_DIR: &str = "vendored";
+const MANIFEST_NAME: &str = "manifest.toml";
+const GENERATED_NAME: &str = "vendored_manifest.rs";
+
+struct Entry {
+  name: String,
+  version: String,
+  sha256: String
+}
+
+fn _field(
+  archive: &toml::Value,
+  key: &str
+) -> String {
+  archive.get(key)
+    .and_then(|x| x.as_str())
+    .unwrap_or_else(|| panic!("Every archive in {} has to have `{}`", MANIFEST_NAME, key))
+    .to_string()
+}
+
+fn _read_manifest(
+  path: &Path
+) -> Vec<Entry> {
+  let contents = match fs::read_to_string(path) {
+    Ok(contents) => contents,
+    Err(_) => {
+      println!(
+        "cargo:warning=`{}` does not exist, no vendored archive will be available",
+        path.display()
+      );
+      return vec![]
+    }
+  };
+  let manifest = contents.parse::<toml::Value>()
+    .unwrap_or_else(|e| panic!("`{}` could not be parsed ({})", path.display(), e));
+  manifest.get("archive")
+    .and_then(|x| x.as_array())
+    .map_or_else(Vec::new, |archives| archives.iter().map(|archive| Entry {
+      name: _field(archive, "name"),
+      version: _field(archive, "version"),
+      sha256: _field(archive, "sha256").to_lowercase()
+    }).collect())
+}
+
+fn _sha256(
+  path: &Path
+) -> String {
+  let contents = fs::read(path)
+    .unwrap_or_else(|e| panic!("`{}` could not be read ({})", path.display(), e));
+  Sha256::digest(&contents).iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+// Stale or hand-copied archives fail the build,
+// instead of the installation
+fn _verify(
+  dir: &Path,
+  entries: &[Entry]
+) {
+  let mut listed = HashSet::new();
+  for entry in entries {
+    let digest = _sha256(&dir.join(&entry.name));
+    if digest != entry.sha256 {
+      panic!(
+        "`{}` does not match {} (expected sha256 {}, got {})",
+        entry.name,
+        MANIFEST_NAME,
+        entry.sha256,
+        digest
+      );
+    }
+    listed.insert(entry.name.clone());
+  }
+  for file in fs::read_dir(dir).into_iter().flatten().flatten() {
+    let name = file.file_name().to_string_lossy().to_string();
+    if name != MANIFEST_NAME && !listed.contains(&name) {
+      panic!("`{}` is not listed in {}", name, MANIFEST_NAME);
+    }
+  }
+}
+
+fn main() {
+  let dir = Path::new(VENDORED_DIR);
+  // RustEmbed requires the folder to exist, even when empty
+  fs::create_dir_all(dir).expect("Vendored directory could not be created");
+  println!("cargo:rerun-if-changed={}", VENDORED_DIR);
+  let entries = _read_manifest(&dir.join(MANIFEST_NAME));
+  _verify(dir, &entries);
+
+  let generated = entries
+    .iter()
+    .map(|entry| format!(
+      "  VendoredArchive {{ name: {:?}, version: {:?}, sha256: {:?} }},\n",
+      entry.name,
+      entry.version,
+      entry.sha256
+    ))
+    .collect::<String>();
+  let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+  fs::write(
+    Path::new(&out_dir).join(GENERATED_NAME),
+    format!("pub const MANIFEST: &[VendoredArchive] = &[\n{}];\n", generated)
+  ).expect("Generated manifest could not be written");
+}
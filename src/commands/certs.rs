@@ -0,0 +1,64 @@
+use std::fs::{read, read_dir};
+use std::path::{Path, PathBuf};
+
+use openssl::asn1::Asn1Time;
+use openssl::x509::X509;
+
+use crate::commands::{create_host, GlobalOptions};
+use crate::errors::InstallError;
+use crate::fs::rooted;
+use crate::host::Host;
+use crate::install_ctx::InstallCtx;
+use crate::pki::cert::common_name;
+
+fn _find_pem_files(
+  dir: &Path,
+  found: &mut Vec<PathBuf>
+) -> Result<(), InstallError> {
+  if !dir.is_dir() {
+    return Ok(())
+  }
+  for entry in read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      _find_pem_files(&path, found)?;
+    }
+    else if path.extension().is_some_and(|x| x == "pem") {
+      found.push(path);
+    }
+  }
+  Ok(())
+}
+
+// Lists every certificate found under installation dir,
+// along with the number of days left until it expires
+pub fn certs(
+  global: &GlobalOptions
+) -> Result<(), InstallError> {
+  let install_ctx = InstallCtx::new(
+    &global.config,
+    create_host(global)
+  )?;
+  let installation_dir = rooted(
+    install_ctx.host.root(),
+    Path::new(&install_ctx.config.installation_dir)
+  );
+  let mut pem_files = vec![];
+  _find_pem_files(&installation_dir, &mut pem_files)?;
+  pem_files.sort();
+
+  let now = Asn1Time::days_from_now(0)?;
+  for path in pem_files {
+    // Private keys share the extension
+    let certificate = match X509::from_pem(&read(&path)?) {
+      Ok(certificate) => certificate,
+      Err(_) => continue
+    };
+    let days_left = now.diff(certificate.not_after())?.days;
+    println!("{}", path.display());
+    println!("  subject:   {}", common_name(certificate.subject_name()));
+    println!("  issuer:    {}", common_name(certificate.issuer_name()));
+    println!("  not after: {} ({} days left)", certificate.not_after(), days_left);
+  }
+  Ok(())
+}
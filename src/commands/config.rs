@@ -0,0 +1,116 @@
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use crate::commands::GlobalOptions;
+use crate::config::{Settings, API_VERSION};
+use crate::config::defaults::defaults_toml;
+use crate::config::migration::{self, ConfigFormat};
+use crate::config::schema::settings_schema_json;
+use crate::config::sources::{effective_settings, ConfigFile};
+use crate::errors::{ErrorKind, InstallError};
+use crate::fs::{write_atomic, Owner};
+
+// Prints configuration, as seen by the installer.
+// Effective one lists every value with its source instead
+pub fn show(
+  global: &GlobalOptions,
+  effective: bool
+) -> Result<(), InstallError> {
+  if !effective {
+    let settings = Settings::new(&global.config)?;
+    print!("{}", settings.to_toml()?);
+    return Ok(())
+  }
+  let values = effective_settings(&global.config)?
+    .into_iter()
+    .map(|(key, value, source)| (format!("{} = {}", key, value), source))
+    .collect::<Vec<(String, String)>>();
+  let width = values.iter().map(|(x, _)| x.len()).max().unwrap_or(0);
+  for (assignment, source) in values {
+    println!("{:width$}  # {}", assignment, source, width = width);
+  }
+  Ok(())
+}
+
+// Reports every configuration error at once
+pub fn validate(
+  global: &GlobalOptions
+) -> Result<(), InstallError> {
+  let settings = Settings::new(&global.config)?;
+  for warning in global.config.warnings()? {
+    eprintln!("Warning: {}", warning);
+  }
+  settings.validate()?;
+  println!("Configuration is valid");
+  Ok(())
+}
+
+pub fn print_defaults() -> Result<(), InstallError> {
+  println!("{}", defaults_toml()?);
+  Ok(())
+}
+
+pub fn schema() -> Result<(), InstallError> {
+  println!("{}", settings_schema_json()?);
+  Ok(())
+}
+
+// Upgrades --config file to current api_version, the result
+// is printed, unless the file should be changed in place
+pub fn migrate(
+  global: &GlobalOptions,
+  in_place: bool
+) -> Result<(), InstallError> {
+  let (contents, format, path) = match &global.config.file {
+    Some(ConfigFile::Path(path)) => (
+      fs::read_to_string(path)?,
+      ConfigFormat::from_path(Path::new(path))?,
+      Some(path)
+    ),
+    Some(ConfigFile::Contents(contents)) => (
+      contents.clone(),
+      ConfigFormat::guess(contents),
+      None
+    ),
+    None => return Err(
+      InstallError::new_from_str(
+        ErrorKind::Config,
+        "Configuration file to migrate has to be given with --config"
+      )
+    )
+  };
+
+  let (migrated, notes) = migration::migrate(&contents, format)?;
+  if notes.is_empty() {
+    eprintln!("Configuration is already at api_version `{}`", API_VERSION);
+  }
+  for note in notes {
+    eprintln!("{}", note);
+  }
+  match (in_place, path) {
+    // Keeps mode and owner of the file, as it may refer to secrets
+    (true, Some(path)) => {
+      let metadata = fs::metadata(path)?;
+      let owner = Owner { uid: metadata.uid(), gid: metadata.gid() };
+      Ok(
+        write_atomic(
+          Path::new(path),
+          migrated.as_bytes(),
+          metadata.permissions().mode() & 0o7777,
+          Some(owner)
+        )?
+      )
+    },
+    (true, None) => Err(
+      InstallError::new_from_str(
+        ErrorKind::Config,
+        "Configuration read from stdin can not be migrated in place"
+      )
+    ),
+    (false, _) => {
+      print!("{}", migrated);
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,18 @@
+use crate::commands::{create_host, GlobalOptions};
+use crate::components::etcd::etcdctl;
+use crate::errors::InstallError;
+use crate::install_ctx::InstallCtx;
+
+// Passes arguments through to etcdctl,
+// connected to the local member
+pub fn etcd(
+  global: &GlobalOptions,
+  args: &[&str]
+) -> Result<(), InstallError> {
+  let install_ctx = InstallCtx::new(
+    &global.config,
+    create_host(global)
+  )?;
+  print!("{}", etcdctl(&install_ctx, args)?);
+  Ok(())
+}
@@ -0,0 +1,163 @@
+use log::info;
+use url::Url;
+
+use crate::commands::{create_components, create_host, GlobalOptions};
+use crate::components::InstallStepResult;
+use crate::components::runner::{run_components, ComponentSelection, RunOptions};
+use crate::config::{ClusterState, EtcdNode};
+use crate::errors::{ErrorKind, InstallError};
+use crate::install_ctx::InstallCtx;
+
+#[derive(Debug, Default)]
+pub struct InstallOptions {
+  pub custom_ca_pkey_path: Option<String>,
+  pub custom_ca_cert_path: Option<String>,
+  pub should_fix: bool,
+  pub selection: ComponentSelection,
+  pub run_options: RunOptions
+}
+
+#[derive(Debug, Default)]
+pub struct JoinOptions {
+  // Members of existing cluster, override ones from config
+  pub peers: Vec<EtcdNode>,
+  pub install: InstallOptions
+}
+
+// Peers are given as `name=peer_url`
+pub fn parse_peer(
+  peer: &str
+) -> Result<EtcdNode, InstallError> {
+  let mut parts = peer.splitn(2, '=');
+  match (parts.next(), parts.next()) {
+    (Some(name), Some(peer_url)) if !name.is_empty() => Ok(
+      EtcdNode {
+        name: name.to_string(),
+        peer_url: Url::parse(peer_url).map_err(|e|
+          InstallError::new(
+            ErrorKind::Config,
+            format!("Peer `{}` has invalid peer_url ({})", peer, e)
+          )
+        )?
+      }
+    ),
+    _ => Err(
+      InstallError::new(
+        ErrorKind::Config,
+        format!("Peer `{}` should be given as name=peer_url", peer)
+      )
+    )
+  }
+}
+
+fn _install(
+  global: &GlobalOptions,
+  options: &InstallOptions,
+  install_ctx: InstallStepResult
+) -> Result<(), InstallError> {
+  let components = create_components(
+    options.custom_ca_pkey_path.as_deref(),
+    options.custom_ca_cert_path.as_deref(),
+    options.should_fix
+  );
+  // Failed context is passed through as well, so the report gets written
+  run_components(install_ctx, &components, &options.selection, &options.run_options)?;
+  if global.dry_run {
+    info!("Dry run has finished, no changes were made to the host");
+  }
+  Ok(())
+}
+
+pub fn install(
+  global: &GlobalOptions,
+  options: &InstallOptions
+) -> Result<(), InstallError> {
+  let install_ctx = InstallCtx::new_with_init(
+    &global.config,
+    &global.log,
+    create_host(global)
+  );
+  _install(global, options, install_ctx)
+}
+
+// Certificates of joining node have to be signed
+// with the CA, that the rest of cluster trusts
+fn _join_ctx(
+  global: &GlobalOptions,
+  options: &JoinOptions
+) -> InstallStepResult {
+  let has_custom_ca = options.install.custom_ca_pkey_path.is_some()
+    && options.install.custom_ca_cert_path.is_some();
+  if !has_custom_ca {
+    return Err(InstallError::custom_ca_not_set())
+  }
+
+  let mut install_ctx = InstallCtx::new_with_init(
+    &global.config,
+    &global.log,
+    create_host(global)
+  )?;
+  if !options.peers.is_empty() {
+    install_ctx.config.etcd.other_nodes = Some(options.peers.clone());
+  }
+  let has_peers = install_ctx.config.etcd.other_nodes.as_ref().is_some_and(
+    |nodes| !nodes.is_empty()
+  );
+  if !has_peers {
+    return Err(
+      InstallError::new_from_str(
+        ErrorKind::Config,
+        "Joining requires at least one member of existing cluster"
+      )
+    )
+  }
+  install_ctx.config.etcd.initial_cluster_state = ClusterState::Existing;
+  // Peers given on command line have not been validated yet
+  install_ctx.config.validate()?;
+  Ok(install_ctx)
+}
+
+pub fn join(
+  global: &GlobalOptions,
+  options: &JoinOptions
+) -> Result<(), InstallError> {
+  _install(global, &options.install, _join_ctx(global, options))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_peer() {
+    let peer = parse_peer("schooner=https://10.0.0.2:2380").unwrap();
+    assert_eq!(peer.name, "schooner");
+    assert_eq!(peer.peer_url.as_str(), "https://10.0.0.2:2380/");
+    assert!(parse_peer("schooner").is_err());
+    assert!(parse_peer("=https://10.0.0.2:2380").is_err());
+    assert!(parse_peer("schooner=10.0.0.2").is_err());
+  }
+
+  #[test]
+  fn test_report_is_written_when_join_is_rejected() {
+    let report_dir = tempfile::TempDir::new().unwrap();
+    let report_path = report_dir.path().join("report.json");
+    let options = JoinOptions {
+      install: InstallOptions {
+        run_options: RunOptions { report_path: Some(report_path.clone()), ..Default::default() },
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+    assert!(join(&GlobalOptions { dry_run: true, ..Default::default() }, &options).is_err());
+
+    let report: serde_json::Value = serde_json::from_slice(
+      &std::fs::read(&report_path).unwrap()
+    ).unwrap();
+    assert_eq!(report["succeeded"], false);
+    assert_eq!(
+      report["error"]["message"],
+      "Custom CA was not provided for the installer run."
+    );
+  }
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use crate::components::Component;
+use crate::components::ca::CaComponent;
+use crate::components::etcd::EtcdComponent;
+use crate::components::global_validation::GlobalValidationComponent;
+use crate::components::preflight::{
+  NetworkPreflightComponent,
+  SystemPreflightComponent
+};
+use crate::config::sources::ConfigSources;
+use crate::host::Host;
+use crate::host::recording::RecordingHost;
+use crate::host::system::SystemHost;
+use crate::logging::LogOptions;
+
+pub mod certs;
+pub mod config;
+pub mod etcd;
+pub mod install;
+pub mod reset;
+pub mod status;
+pub mod vendored;
+
+// Flags shared by every subcommand
+#[derive(Debug, Default)]
+pub struct GlobalOptions {
+  pub config: ConfigSources,
+  pub log: LogOptions,
+  pub root: Option<PathBuf>,
+  pub dry_run: bool
+}
+
+pub fn version() -> &'static str {
+  env!("CARGO_PKG_VERSION")
+}
+
+pub fn create_host(
+  options: &GlobalOptions
+) -> Box<dyn Host> {
+  match (options.dry_run, &options.root) {
+    (true, Some(root)) => Box::new(RecordingHost::with_root(root)),
+    (true, None) => Box::new(RecordingHost::new()),
+    (false, Some(root)) => Box::new(SystemHost::with_root(root)),
+    (false, None) => Box::new(SystemHost::new())
+  }
+}
+
+pub fn create_components(
+  custom_ca_pkey_path: Option<&str>,
+  custom_ca_cert_path: Option<&str>,
+  should_fix: bool
+) -> Vec<Box<dyn Component>> {
+  vec![
+    Box::new(GlobalValidationComponent),
+    Box::new(NetworkPreflightComponent),
+    Box::new(SystemPreflightComponent { should_fix }),
+    Box::new(CaComponent::new(custom_ca_pkey_path, custom_ca_cert_path)),
+    Box::new(EtcdComponent),
+  ]
+}
@@ -0,0 +1,54 @@
+use std::io::{self, BufRead, Write};
+
+use crate::commands::{create_components, create_host, GlobalOptions};
+use crate::components::Component;
+use crate::components::reset::{reset_components, reset_plan, ResetOptions};
+use crate::errors::InstallError;
+use crate::install_ctx::InstallCtx;
+
+#[derive(Debug, Default)]
+pub struct ResetCommandOptions {
+  // Skips confirmation prompt
+  pub assume_yes: bool,
+  pub reset: ResetOptions
+}
+
+fn _confirm_reset(
+  install_ctx: &InstallCtx,
+  components: &[Box<dyn Component>],
+  options: &ResetOptions
+) -> Result<bool, InstallError> {
+  let plan = reset_plan(install_ctx, components, options)?;
+  if plan.is_empty() {
+    println!("Nothing to remove, node is already clean");
+    return Ok(false)
+  }
+  println!("Following will be removed from the node:");
+  for artifact in plan.iter() {
+    println!("  - {}", artifact);
+  }
+  print!("Do you want to continue? [y/N] ");
+  io::stdout().flush()?;
+
+  let mut answer = String::new();
+  io::stdin().lock().read_line(&mut answer)?;
+  Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn reset(
+  global: &GlobalOptions,
+  options: &ResetCommandOptions
+) -> Result<(), InstallError> {
+  let install_ctx = InstallCtx::new_with_init(
+    &global.config,
+    &global.log,
+    create_host(global)
+  )?;
+  let components = create_components(None, None, false);
+
+  let should_ask = !global.dry_run && !options.assume_yes;
+  if should_ask && !_confirm_reset(&install_ctx, &components, &options.reset)? {
+    return Ok(())
+  }
+  reset_components(Ok(install_ctx), &components, &options.reset)
+}
@@ -0,0 +1,65 @@
+use crate::commands::{create_components, create_host, GlobalOptions};
+use crate::errors::InstallError;
+use crate::host::Host;
+use crate::host::tracking::Artifact;
+use crate::install_ctx::InstallCtx;
+use crate::journal::{Journal, StepStatus};
+
+fn _unit_state(
+  host: &dyn Host,
+  unit: &str
+) -> String {
+  // There is no running systemd inside of an image
+  if host.root().is_some() {
+    return match host.is_unit_enabled(unit) {
+      true => "enabled".to_string(),
+      false => "disabled".to_string()
+    }
+  }
+  host.run_command("systemctl", &["is-active", unit]).map_or_else(
+    |_| "unknown".to_string(),
+    |output| String::from_utf8_lossy(&output.stdout).trim().to_string()
+  )
+}
+
+// Prints state of every component, as recorded
+// in the journal, along with state of installed units
+pub fn status(
+  global: &GlobalOptions
+) -> Result<(), InstallError> {
+  let install_ctx = InstallCtx::new(
+    &global.config,
+    create_host(global)
+  )?;
+  let journal_path = Journal::path(&install_ctx.config);
+  let journal = Journal::load(&*install_ctx.host, &journal_path)?;
+
+  println!("Journal: {}", journal_path.display());
+  println!("Components:");
+  let mut units = vec![];
+  for component in create_components(None, None, false) {
+    let state = match journal.components.get(component.name()) {
+      Some(entry) => {
+        units.extend(entry.artifacts.iter().filter_map(|x| match x {
+          Artifact::Unit { name } => Some(name.clone()),
+          _ => None
+        }));
+        match entry.status {
+          StepStatus::Completed => "completed",
+          StepStatus::Failed => "failed"
+        }
+      },
+      None if component.is_resumable() => "not installed",
+      None => "not journaled"
+    };
+    println!("  {:<20} {}", component.name(), state);
+  }
+
+  if !units.is_empty() {
+    println!("Units:");
+    for unit in units {
+      println!("  {:<20} {}", unit, _unit_state(&*install_ctx.host, &unit));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,22 @@
+use crate::errors::InstallError;
+use crate::vendored::{is_embedded, MANIFEST};
+
+// Prints archives embedded in the binary, so that
+// it can be audited, which versions it carries
+pub fn list() -> Result<(), InstallError> {
+  println!("{:<24} {:<10} sha256", "archive", "version");
+  for archive in MANIFEST {
+    let missing = match is_embedded(archive.name) {
+      true => "",
+      false => " (not embedded)"
+    };
+    println!(
+      "{:<24} {:<10} {}{}",
+      archive.name,
+      archive.version,
+      archive.sha256,
+      missing
+    );
+  }
+  Ok(())
+}
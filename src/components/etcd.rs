@@ -1,33 +1,46 @@
 use std::collections::HashSet;
 use std::ffi::OsString;
-use std::fs::create_dir_all;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use askama::Template;
-
-use crate::components::ca::{get_ca_cert_full_path};
-use crate::components::InstallStepResult;
-use crate::config::EtcdNode;
-use crate::errors::{ErrorKind, InstallError};
-use crate::fs::flatten;
+use log::{info, warn};
+
+use crate::components::ca::{get_ca_cert_full_path, CA_COMPONENT_NAME};
+use crate::components::preflight::{
+  NETWORK_PREFLIGHT_COMPONENT_NAME,
+  SYSTEM_PREFLIGHT_COMPONENT_NAME
+};
+use crate::components::reset::ResetOptions;
+use crate::components::{Component, InstallStepResult};
+use crate::config::{ClusterState, EtcdNode};
+use crate::errors::{Context, ErrorKind, InstallError};
+use crate::fs::Owner;
+use crate::host::Host;
+use crate::host::tracking::Artifact;
 use crate::install_ctx::InstallCtx;
 use crate::pki::cert::create_ca_signed_certificate;
 use crate::pki::io::{save_as_pem_private_key, save_as_pem_certificate};
+use crate::report::Endpoint;
 use crate::templates::render_and_save;
-use crate::vendored::unpack_archive;
-
-const ETCD_DIRNAME: &'static str = "etcd";
-const ETCD_ARCHIVE_NAME: &'static str = "etcd.tar.gz";
-const ETCD_BINARY_NAME: &'static str = "etcd";
-const ETCD_CERT_DIRNAME: &'static str = "certs";
-const ETCD_CLIENT_PKEY_PATH: &'static str = "etcd-client.private-key.pem";
-const ETCD_CLIENT_CERT_PATH: &'static str = "etcd-client.pem";
-const ETCD_PEER_PKEY_PATH: &'static str = "etcd-peer.private-key.pem";
-const ETCD_PEER_CERT_PATH: &'static str = "etcd-peer.pem";
-const ETCD_CFG_FILE_NAME: &'static str = "etcd.conf.yml";
-const ETCD_SYSTEMD_DEF_PATH: &'static str = "/etc/systemd/system/etcd.service";
-const ETCDCTL_BINARY_NAME: &'static str = "etcdctl";
+use crate::vendored::ArtifactRequest;
+
+pub const ETCD_COMPONENT_NAME: &str = "etcd";
+
+const ETCD_DIRNAME: &str = "etcd";
+const ETCD_ARTIFACT_NAME: &str = "etcd";
+const ETCD_BINARY_NAME: &str = "etcd";
+const ETCD_CERT_DIRNAME: &str = "certs";
+const ETCD_CLIENT_PKEY_PATH: &str = "etcd-client.private-key.pem";
+const ETCD_CLIENT_CERT_PATH: &str = "etcd-client.pem";
+const ETCD_PEER_PKEY_PATH: &str = "etcd-peer.private-key.pem";
+const ETCD_PEER_CERT_PATH: &str = "etcd-peer.pem";
+const ETCD_CFG_FILE_NAME: &str = "etcd.conf.yml";
+// Owns certificates, when the host has such user and group
+const ETCD_USER_NAME: &str = "etcd";
+const ETCD_SYSTEMD_DEF_PATH: &str = "/etc/systemd/system/etcd.service";
+pub const ETCD_SYSTEMD_UNIT_NAME: &str = "etcd.service";
+const ETCDCTL_BINARY_NAME: &str = "etcdctl";
 
 #[derive(Template)]
 #[template(path = "etcd/etcd.service", escape = "none")]
@@ -46,7 +59,7 @@ struct EtcdConfigFileTemplate<'a> {
   listen_client_urls: &'a String,
   initial_cluster: &'a String,
   cluster_token:  &'a str,
-  initial_cluster_state: &'a String,
+  initial_cluster_state: &'a str,
   ca_path:  &'a str,
   client_cert_path:  &'a str,
   client_cert_key_path:  &'a str,
@@ -59,7 +72,6 @@ fn _get_etcd_files_to_extract() -> HashSet<OsString> {
   let mut etcd_artifacts_names = HashSet::new();
   etcd_artifacts_names.insert(OsString::from("etcd"));
   etcd_artifacts_names.insert(OsString::from("etcdctl"));
-  let etcd_artifacts_names = etcd_artifacts_names;
   etcd_artifacts_names
 }
 
@@ -138,11 +150,11 @@ fn _get_initial_cluster(
   ];
   let other_nodes: Vec<EtcdNode> = vec![];
   let other_nodes = &install_ctx.config.etcd.other_nodes.as_ref().unwrap_or(&other_nodes);
-  for node in other_nodes.into_iter() {
+  for node in other_nodes.iter() {
     initial_cluster.push(
       (
         node.name.clone(),
-        node.peer_url.clone()
+        node.peer_address()
       )
     );
   }
@@ -162,7 +174,7 @@ fn _get_join_endpoints(
   let other_nodes = install_ctx.config.etcd.other_nodes.as_ref().unwrap_or(&other_nodes).clone();
   Ok(
     other_nodes.into_iter()
-    .map(|n| n.peer_url.clone())
+    .map(|n| n.peer_address())
     .collect::<Vec<String>>()
     .join(",")
   )
@@ -176,7 +188,7 @@ fn _stringify(
       ErrorKind::Other,
       format!("Could not stringify `{:#?}`", &path)
     )),
-    |x| Ok(x)
+    Ok
   )
 }
 
@@ -189,52 +201,72 @@ fn _ensure_certificates_exit(
 ) -> Result<(), InstallError> {
   let ca_private_key = install_ctx.ca_private_key.as_ref().map_or_else(
     || Err(InstallError::new_from_str(ErrorKind::Other, "CA private key not found in install_ctx")),
-    |x| Ok(x)
+    Ok
   )?;
   let ca_certificate = install_ctx.ca_certificate.as_ref().map_or_else(
     || Err(InstallError::new_from_str(ErrorKind::Other, "CA cert not found in install_ctx")),
-    |x| Ok(x)
+    Ok
   )?;
-  
+  let owner = _etcd_owner(&*install_ctx.host)?;
+
   let (peer_pkey, peer_cert) = create_ca_signed_certificate(
     &install_ctx.config.pki,
-    &ca_private_key,
-    &ca_certificate,
+    ca_private_key,
+    ca_certificate,
     &install_ctx.config.hostname,
     &365,
     &Some(vec![install_ctx.config.hostname.clone()]),
     &Some(vec![format!("{}", install_ctx.config.bind_address)])
   )?;
   save_as_pem_private_key(
+    &*install_ctx.host,
     &peer_pkey,
-    &path_to_peer_pkey
-  )?;
+    path_to_peer_pkey,
+    owner
+  ).with_context(|| format!("while writing etcd peer private key to {}", path_to_peer_pkey.display()))?;
   save_as_pem_certificate(
+    &*install_ctx.host,
     &peer_cert,
-    &path_to_peer_cert
-  )?;
+    path_to_peer_cert,
+    owner
+  ).with_context(|| format!("while writing etcd peer certificate to {}", path_to_peer_cert.display()))?;
 
   let (client_pkey, client_cert) = create_ca_signed_certificate(
     &install_ctx.config.pki,
-    &ca_private_key,
-    &ca_certificate,
+    ca_private_key,
+    ca_certificate,
     &install_ctx.config.hostname,
     &365,
     &Some(vec![install_ctx.config.hostname.clone()]),
     &Some(vec![format!("{}", install_ctx.config.bind_address)])
   )?;
   save_as_pem_private_key(
+    &*install_ctx.host,
     &client_pkey,
-    &path_to_client_pkey
-  )?;
+    path_to_client_pkey,
+    owner
+  ).with_context(|| format!("while writing etcd client private key to {}", path_to_client_pkey.display()))?;
   save_as_pem_certificate(
+    &*install_ctx.host,
     &client_cert,
-    &path_to_client_cert
-  )?;
+    path_to_client_cert,
+    owner
+  ).with_context(|| format!("while writing etcd client certificate to {}", path_to_client_cert.display()))?;
 
   Ok(())
 }
 
+fn _etcd_owner(
+  host: &dyn Host
+) -> Result<Option<Owner>, InstallError> {
+  match (host.lookup_user(ETCD_USER_NAME)?, host.lookup_group(ETCD_USER_NAME)?) {
+    (Some(uid), Some(gid)) => Ok(Some(Owner { uid, gid })),
+    _ => Ok(None)
+  }
+}
+
+// Every path is laid out by the caller, see `_install_etcd`
+#[allow(clippy::too_many_arguments)]
 fn _create_config_file(
   install_ctx: &InstallCtx,
   path_to_data_dir: &Path,
@@ -256,98 +288,171 @@ fn _create_config_file(
     install_ctx.config.etcd.listen_client_port
   );
 
-  let initial_cluster = _get_initial_cluster(&install_ctx)?;
+  let initial_cluster = _get_initial_cluster(install_ctx)?;
 
   render_and_save(
+    &*install_ctx.host,
     EtcdConfigFileTemplate {
       member_name: &install_ctx.config.hostname,
-      data_dir: &_stringify(&path_to_data_dir)?,
+      data_dir: _stringify(path_to_data_dir)?,
       listen_peer_urls: &listen_peer_url,
       listen_client_urls: &listen_client_url,
       initial_cluster: &initial_cluster,
       cluster_token: "etcd-cluster",
-      initial_cluster_state: &install_ctx.config.etcd.initial_cluster_state,
-      ca_path: &_stringify(&path_to_ca_cert)?,
-      client_cert_path: &_stringify(&path_to_client_cert)?,
-      client_cert_key_path: &_stringify(&path_to_client_pkey)?,
-      peer_cert_path: &_stringify(&path_to_peer_cert)?,
-      peer_cert_key_path: &_stringify(&path_to_peer_pkey)?
+      initial_cluster_state: &install_ctx.config.etcd.initial_cluster_state.to_string(),
+      ca_path: _stringify(path_to_ca_cert)?,
+      client_cert_path: _stringify(path_to_client_cert)?,
+      client_cert_key_path: _stringify(path_to_client_pkey)?,
+      peer_cert_path: _stringify(path_to_peer_cert)?,
+      peer_cert_key_path: _stringify(path_to_peer_pkey)?
     },
-    &path_to_config_file
+    path_to_config_file
   )
 }
 
 fn _create_systemd_service_file(
+  install_ctx: &InstallCtx,
   path_to_config_file: &Path,
   path_to_binary: &Path,
   path_to_root_dir: &Path
 ) -> Result<(), InstallError> {
   render_and_save(
+    &*install_ctx.host,
     EtcdServiceTemplate {
-      config_file_path: &_stringify(&path_to_config_file)?,
-      exec_file_path: &_stringify(&path_to_binary)?,
-      installation_dir: &_stringify(&path_to_root_dir)?
+      config_file_path: _stringify(path_to_config_file)?,
+      exec_file_path: _stringify(path_to_binary)?,
+      installation_dir: _stringify(path_to_root_dir)?
     },
-    &Path::new(ETCD_SYSTEMD_DEF_PATH)
+    Path::new(ETCD_SYSTEMD_DEF_PATH)
   )
 }
 
 fn _join_exiting_cluster(
-  install_ctx: &InstallCtx,
-  path_to_etcdctl: &Path,
-  client_key_path: &Path,
-  client_cert_path: &Path,
-  ca_path: &Path,
+  install_ctx: &InstallCtx
 ) -> Result<(), InstallError> {
-  let join_command = format!(
-    "{path_to_etcdctl} \
-    --endpoints={endpoints} \
-    --key={client_key_path} \
-    --cert={client_cert_path} \
-    --cacert={ca_path} \
-    member add {member_name}",
-    path_to_etcdctl=_stringify(path_to_etcdctl)?,
-    endpoints=_get_join_endpoints(&install_ctx)?,
-    member_name=&install_ctx.config.hostname,
-    client_key_path=_stringify(client_key_path)?,
-    client_cert_path=_stringify(client_cert_path)?,
-    ca_path=_stringify(ca_path)?
-  );
-  
-  let output = Command::new("sh")
-    .arg("-c")
-    .arg(join_command)
-    .output()?;
+  _etcdctl(
+    install_ctx,
+    &_get_join_endpoints(install_ctx)?,
+    &["member", "add", &install_ctx.config.hostname]
+  )?;
+  info!("`{}` has been added to existing etcd cluster", install_ctx.config.hostname);
   Ok(())
 }
 
-fn _enable_systemd_service(
-) -> Result<(), InstallError> {
-  let systemctl_commands = vec![
-    "systemctl daemon-reload",
-    "systemctl enable etcd.service",
-    "systemctl status etcd.service"
+// `etcdctl member list` prints members
+// as `id, status, name, peer urls, client urls, is learner`
+fn _find_member_id(
+  member_list: &str,
+  member_name: &str
+) -> Option<String> {
+  member_list
+    .lines()
+    .map(|line| line.split(',').map(|x| x.trim()).collect::<Vec<&str>>())
+    .find(|fields| fields.len() > 2 && fields[2] == member_name)
+    .map(|fields| fields[0].to_string())
+}
+
+fn _etcdctl(
+  install_ctx: &InstallCtx,
+  endpoints: &str,
+  args: &[&str]
+) -> Result<String, InstallError> {
+  let (.., path_to_client_pkey, path_to_client_cert, _, _, path_to_etcdctl) =
+    _get_etcd_paths(install_ctx);
+  let path_to_ca_cert = get_ca_cert_full_path(install_ctx);
+  let mut etcdctl_args = vec![
+    format!("--endpoints={}", endpoints),
+    format!("--key={}", _stringify(&path_to_client_pkey)?),
+    format!("--cert={}", _stringify(&path_to_client_cert)?),
+    format!("--cacert={}", _stringify(&path_to_ca_cert)?)
   ];
+  etcdctl_args.extend(args.iter().map(|x| x.to_string()));
+
+  let output = install_ctx.host.run_checked(
+    _stringify(&path_to_etcdctl)?,
+    &etcdctl_args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+    ErrorKind::Etcd
+  )?;
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
 
-  for cmd in systemctl_commands {
-    let output = Command::new("sh")
-      .arg("-c")
-      .arg(cmd)
-      .output()?;
-    if !output.status.success() {
-      return Err(
-        InstallError::new(
-          ErrorKind::Systemd,
-          format!("Command {} has failed.", cmd)
-        )
+// Runs etcdctl against the local member, with installer's certificates
+pub fn etcdctl(
+  install_ctx: &InstallCtx,
+  args: &[&str]
+) -> Result<String, InstallError> {
+  let (.., path_to_etcdctl) = _get_etcd_paths(install_ctx);
+  if !install_ctx.host.path_exists(&path_to_etcdctl) {
+    return Err(
+      InstallError::new(
+        ErrorKind::Etcd,
+        format!("`{}` does not exist, is etcd installed?", path_to_etcdctl.display())
       )
-    }
+    )
   }
+  _etcdctl(install_ctx, &_client_url(install_ctx), args)
+}
+
+// IPv6 address has to be put in brackets
+fn _client_url(
+  install_ctx: &InstallCtx
+) -> String {
+  format!(
+    "https://{}",
+    SocketAddr::new(install_ctx.config.bind_address, install_ctx.config.etcd.listen_client_port)
+  )
+}
+
+fn _leave_cluster(
+  install_ctx: &InstallCtx
+) -> Result<(), InstallError> {
+  let hostname = &install_ctx.config.hostname;
+  // Local member still runs, so it is asked over its client API
+  let endpoints = _client_url(install_ctx);
+  let member_list = _etcdctl(install_ctx, &endpoints, &["member", "list"])?;
+  match _find_member_id(&member_list, hostname) {
+    Some(member_id) => {
+      info!("Removing `{}` (member {}) from etcd cluster", hostname, member_id);
+      _etcdctl(install_ctx, &endpoints, &["member", "remove", &member_id])?;
+    },
+    None => warn!("`{}` is not a member of etcd cluster, nothing to remove", hostname)
+  };
   Ok(())
 }
 
-pub fn etcd_component(
-  mut install_ctx: InstallCtx
+fn _uninstall_etcd(
+  install_ctx: InstallCtx,
+  options: &ResetOptions
+) -> InstallStepResult {
+  let (.., path_to_etcdctl) = _get_etcd_paths(&install_ctx);
+  let is_multi_node = install_ctx.config.etcd.other_nodes.as_ref().is_some_and(
+    |nodes| !nodes.is_empty()
+  );
+  // Remaining members have to forget about this one,
+  // otherwise they would lose quorum sooner
+  if is_multi_node && install_ctx.host.root().is_none()
+    && install_ctx.host.path_exists(&path_to_etcdctl) {
+    _leave_cluster(&install_ctx)?;
+    if !options.wipe_data {
+      warn!("etcd data_dir is kept, it has to be wiped before node joins any cluster again");
+    }
+  }
+
+  if install_ctx.host.path_exists(Path::new(ETCD_SYSTEMD_DEF_PATH)) {
+    install_ctx.host.stop_unit(ETCD_SYSTEMD_UNIT_NAME)?;
+  }
+  Ok(install_ctx)
+}
+
+fn _enable_systemd_service(
+  install_ctx: &InstallCtx
+) -> Result<(), InstallError> {
+  install_ctx.host.reload_systemd()?;
+  install_ctx.host.enable_unit(ETCD_SYSTEMD_UNIT_NAME)
+}
+
+fn _install_etcd(
+  install_ctx: InstallCtx
 ) -> InstallStepResult {
   let etcd_artifacts = _get_etcd_files_to_extract();
   let path_to_ca_cert = get_ca_cert_full_path(&install_ctx);
@@ -361,16 +466,23 @@ pub fn etcd_component(
     path_to_client_cert,
     path_to_peer_pkey,
     path_to_peer_cert,
-    path_to_etcdctl
+    _
   ) = _get_etcd_paths(&install_ctx);
 
-  create_dir_all(&path_to_root_dir)?;
-  create_dir_all(&path_to_data_dir)?;
+  install_ctx.host.create_dir_all(&path_to_root_dir)?;
+  install_ctx.host.create_dir_all(&path_to_data_dir)?;
 
-  unpack_archive(ETCD_ARCHIVE_NAME, &path_to_root_dir)?;
-  flatten(&path_to_root_dir, Some(&etcd_artifacts))?;
+  install_ctx.host.unpack_archive(
+    &ArtifactRequest {
+      source: &install_ctx.config.artifacts,
+      name: ETCD_ARTIFACT_NAME,
+      version: install_ctx.config.etcd.version.as_deref()
+    },
+    &path_to_root_dir,
+    Some(&etcd_artifacts)
+  )?;
 
-  create_dir_all(&path_to_certs_dir)?;
+  install_ctx.host.create_dir_all(&path_to_certs_dir)?;
   _ensure_certificates_exit(
     &install_ctx,
     &path_to_client_pkey,
@@ -391,26 +503,301 @@ pub fn etcd_component(
   )?;
 
   _create_systemd_service_file(
+    &install_ctx,
     &path_to_config_file,
     &path_to_binary,
     &path_to_root_dir
   )?;
 
-  let should_attempt_join = (
-    install_ctx.config.etcd.initial_cluster_state == "existing".to_string()
-    && install_ctx.config.etcd.other_nodes.is_some()
-  );
-  if should_attempt_join {
-    _join_exiting_cluster(
-      &install_ctx,
-      &path_to_etcdctl,
-      &path_to_client_pkey,
-      &path_to_client_cert,
-      &path_to_ca_cert
-    )?;
+  let should_attempt_join = install_ctx.config.etcd.initial_cluster_state == ClusterState::Existing
+    && install_ctx.config.etcd.other_nodes.is_some() ;
+  if should_attempt_join && install_ctx.host.root().is_some() {
+    warn!("Installing under custom root, joining existing etcd cluster is skipped");
+  }
+  else if should_attempt_join {
+    _join_exiting_cluster(&install_ctx)?;
   }
 
-  _enable_systemd_service()?;
+  _enable_systemd_service(&install_ctx)?;
 
   Ok(install_ctx)
 }
+
+pub struct EtcdComponent;
+
+impl Component for EtcdComponent {
+  fn name(&self) -> &'static str {
+    ETCD_COMPONENT_NAME
+  }
+
+  fn depends_on(&self) -> Vec<&'static str> {
+    vec![
+      NETWORK_PREFLIGHT_COMPONENT_NAME,
+      SYSTEM_PREFLIGHT_COMPONENT_NAME,
+      CA_COMPONENT_NAME
+    ]
+  }
+
+  fn install(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult {
+    _install_etcd(install_ctx)
+  }
+
+  fn verify(
+    &self,
+    install_ctx: &InstallCtx
+  ) -> Result<(), InstallError> {
+    install_ctx.host.check_unit_status(ETCD_SYSTEMD_UNIT_NAME)
+  }
+
+  fn endpoints(
+    &self,
+    install_ctx: &InstallCtx
+  ) -> Vec<Endpoint> {
+    vec![
+      Endpoint {
+        name: "etcd-client".to_string(),
+        url: _client_url(install_ctx)
+      }
+    ]
+  }
+
+  fn installed_artifacts(
+    &self,
+    install_ctx: &InstallCtx,
+    options: &ResetOptions
+  ) -> Vec<Artifact> {
+    let (
+      path_to_root_dir,
+      path_to_data_dir,
+      path_to_certs_dir,
+      path_to_binary,
+      path_to_config_file,
+      ..,
+      path_to_etcdctl
+    ) = _get_etcd_paths(install_ctx);
+    // Data dir may be placed within the root dir (as it is by
+    // default), then the latter can not be removed as a whole
+    let mut artifacts = vec![];
+    if options.wipe_data || !path_to_data_dir.starts_with(&path_to_root_dir) {
+      artifacts.push(Artifact::Directory { path: path_to_root_dir });
+    }
+    if options.wipe_data {
+      artifacts.push(Artifact::Directory { path: path_to_data_dir });
+    }
+    artifacts.push(Artifact::File { path: path_to_binary });
+    artifacts.push(Artifact::File { path: path_to_etcdctl });
+    artifacts.push(Artifact::Directory { path: path_to_certs_dir });
+    artifacts.push(Artifact::File { path: path_to_config_file });
+    artifacts.push(Artifact::File { path: PathBuf::from(ETCD_SYSTEMD_DEF_PATH) });
+    artifacts.push(Artifact::Unit { name: ETCD_SYSTEMD_UNIT_NAME.to_string() });
+    artifacts
+  }
+
+  fn uninstall(
+    &self,
+    install_ctx: InstallCtx,
+    options: &ResetOptions
+  ) -> InstallStepResult {
+    _uninstall_etcd(install_ctx, options)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use url::Url;
+  use crate::config::sources::ConfigSources;
+  use crate::components::ca::CaComponent;
+  use crate::components::global_validation::GlobalValidationComponent;
+  use crate::components::preflight::{NetworkPreflightComponent, SystemPreflightComponent};
+  use crate::components::reset::{reset_components, reset_plan};
+  use crate::fs::FileAttributes;
+  use crate::host::fake::FakeHost;
+  use super::*;
+
+  fn _create_ctx_with_ca(
+    host: &FakeHost
+  ) -> InstallCtx {
+    let mut ctx = InstallCtx::new(&ConfigSources::default(), Box::new(host.clone())).unwrap();
+    ctx.config.hostname = "yacht".to_string();
+    ctx.config.installation_dir = "/opt/rusty-sailor".to_string();
+    ctx.config.pki.rsa_size = 2048;
+    host.create_dir_all(Path::new("/etc/systemd/system")).unwrap();
+    CaComponent::new(None, None).install(ctx).unwrap()
+  }
+
+  #[test]
+  fn test_single_node_installation() {
+    let host = FakeHost::new();
+    let ctx = _create_ctx_with_ca(&host);
+    EtcdComponent.install(ctx).unwrap();
+
+    let config_file = host.file_contents(
+      Path::new("/opt/rusty-sailor/etcd/etcd.conf.yml")
+    ).unwrap();
+    assert!(config_file.contains("name: \"yacht\""));
+    assert!(config_file.contains("initial-cluster-state: \"new\""));
+
+    let service_file = host.file_contents(Path::new(ETCD_SYSTEMD_DEF_PATH)).unwrap();
+    assert!(service_file.contains(
+      "ExecStart=/opt/rusty-sailor/etcd/etcd \
+      --config-file=/opt/rusty-sailor/etcd/etcd.conf.yml"
+    ));
+
+    let state = host.state();
+    assert_eq!(
+      state.unpacked_archives,
+      vec![(ETCD_ARTIFACT_NAME.to_string(), PathBuf::from("/opt/rusty-sailor/etcd"))]
+    );
+    assert!(state.files.contains_key(
+      Path::new("/opt/rusty-sailor/etcd/certs/etcd-peer.pem")
+    ));
+    assert_eq!(
+      state.attributes[Path::new("/opt/rusty-sailor/etcd/certs/etcd-peer.private-key.pem")],
+      FileAttributes::PRIVATE
+    );
+    assert!(state.enabled_units.contains(ETCD_SYSTEMD_UNIT_NAME));
+    assert!(!state.commands.iter().any(|x| x.contains(&"member".to_string())));
+  }
+
+  #[test]
+  fn test_certificates_are_owned_by_etcd_user() {
+    let host = FakeHost::new();
+    host.state_mut().users.insert(ETCD_USER_NAME.to_string(), 998);
+    host.state_mut().groups.insert(ETCD_USER_NAME.to_string(), 996);
+    let ctx = _create_ctx_with_ca(&host);
+    EtcdComponent.install(ctx).unwrap();
+
+    let state = host.state();
+    let owner = Some(Owner { uid: 998, gid: 996 });
+    assert_eq!(
+      state.attributes[Path::new("/opt/rusty-sailor/etcd/certs/etcd-peer.private-key.pem")],
+      FileAttributes { owner, ..FileAttributes::PRIVATE }
+    );
+    assert_eq!(
+      state.attributes[Path::new("/opt/rusty-sailor/etcd/certs/etcd-client.pem")],
+      FileAttributes { owner, ..FileAttributes::PUBLIC }
+    );
+    // CA is shared by every component
+    assert_eq!(
+      state.attributes[Path::new("/opt/rusty-sailor/pki/rusty-sailor-ca.pem")].owner,
+      None
+    );
+  }
+
+  #[test]
+  fn test_joining_existing_cluster() {
+    let host = FakeHost::new();
+    let mut ctx = _create_ctx_with_ca(&host);
+    ctx.config.etcd.initial_cluster_state = ClusterState::Existing;
+    ctx.config.etcd.other_nodes = Some(vec![
+      EtcdNode {
+        name: "schooner".to_string(),
+        peer_url: Url::parse("https://10.0.0.2:2380").unwrap()
+      }
+    ]);
+    EtcdComponent.install(ctx).unwrap();
+
+    let state = host.state();
+    let join_command = state.commands.iter().find(
+      |x| x[0] == "/opt/rusty-sailor/etcd/etcdctl"
+    ).unwrap();
+    assert!(join_command.contains(&"--endpoints=https://10.0.0.2:2380".to_string()));
+    assert_eq!(join_command[join_command.len() - 3..], ["member", "add", "yacht"]);
+  }
+
+  #[test]
+  fn test_systemd_failure_is_reported() {
+    let host = FakeHost::new();
+    host.fail_command("systemctl status etcd.service");
+    let ctx = _create_ctx_with_ca(&host);
+
+    let ctx = EtcdComponent.install(ctx).unwrap();
+    let error = EtcdComponent.verify(&ctx).err().unwrap();
+    assert!(matches!(error.kind, ErrorKind::Systemd));
+  }
+
+  #[test]
+  fn test_reset_leaves_cluster_and_removes_etcd() {
+    let host = FakeHost::new();
+    let mut ctx = _create_ctx_with_ca(&host);
+    ctx.config.etcd.other_nodes = Some(vec![
+      EtcdNode {
+        name: "schooner".to_string(),
+        peer_url: Url::parse("https://10.0.0.2:2380").unwrap()
+      }
+    ]);
+    let ctx = EtcdComponent.install(ctx).unwrap();
+    let endpoints = format!("--endpoints={}", _client_url(&ctx));
+    host.set_command_output(
+      "/opt/rusty-sailor/etcd/etcdctl",
+      "8e9e05c52164694d, started, yacht, https://10.0.0.1:2380, https://10.0.0.1:2379, false\n\
+      91bc3c398fb3c146, started, schooner, https://10.0.0.2:2380, https://10.0.0.2:2379, false\n"
+    );
+
+    let options = ResetOptions { wipe_data: true, wipe_pki: false };
+    let components: Vec<Box<dyn Component>> = vec![
+      Box::new(GlobalValidationComponent),
+      Box::new(NetworkPreflightComponent),
+      Box::new(SystemPreflightComponent { should_fix: false }),
+      Box::new(CaComponent::new(None, None)),
+      Box::new(EtcdComponent)
+    ];
+    let plan = reset_plan(&ctx, &components, &options).unwrap();
+    assert_eq!(plan.len(), 8);
+    reset_components(Ok(ctx), &components, &options).unwrap();
+
+    let state = host.state();
+    let remove_command = state.commands.iter().find(
+      |x| x.contains(&"remove".to_string())
+    ).unwrap();
+    assert_eq!(remove_command[remove_command.len() - 3..], ["member", "remove", "8e9e05c52164694d"]);
+    // Client API of the local member, not peer URLs of other ones
+    assert!(remove_command.contains(&endpoints));
+    assert!(endpoints.ends_with(":2379"));
+    assert!(state.commands.contains(&vec!["systemctl".to_string(), "stop".to_string(), ETCD_SYSTEMD_UNIT_NAME.to_string()]));
+    assert!(state.enabled_units.is_empty());
+    assert!(!state.files.keys().any(|x| x.starts_with("/opt/rusty-sailor/etcd")));
+    assert!(!state.files.contains_key(Path::new(ETCD_SYSTEMD_DEF_PATH)));
+    assert!(state.files.keys().any(|x| x.starts_with("/opt/rusty-sailor/pki")));
+  }
+
+  #[test]
+  fn test_reset_keeps_data_dir_within_installation_dir() {
+    let path_to_data_dir = PathBuf::from("/tmp/rusty-sailor/etcd/data/etcd");
+    for wipe_pki in [false, true].iter() {
+      let host = FakeHost::new();
+      let mut ctx = InstallCtx::new(&ConfigSources::default(), Box::new(host.clone())).unwrap();
+      ctx.config.hostname = "yacht".to_string();
+      ctx.config.pki.rsa_size = 2048;
+      host.create_dir_all(Path::new("/etc/systemd/system")).unwrap();
+      let ctx = CaComponent::new(None, None).install(ctx).unwrap();
+      let ctx = EtcdComponent.install(ctx).unwrap();
+      assert!(host.path_exists(&path_to_data_dir));
+
+      let options = ResetOptions { wipe_data: false, wipe_pki: *wipe_pki };
+      let components: Vec<Box<dyn Component>> = vec![
+        Box::new(GlobalValidationComponent),
+        Box::new(NetworkPreflightComponent),
+        Box::new(SystemPreflightComponent { should_fix: false }),
+        Box::new(CaComponent::new(None, None)),
+        Box::new(EtcdComponent)
+      ];
+      let plan = reset_plan(&ctx, &components, &options).unwrap();
+      assert!(!plan.iter().any(|x| match x {
+        Artifact::Directory { path } => path_to_data_dir.starts_with(path),
+        _ => false
+      }));
+      reset_components(Ok(ctx), &components, &options).unwrap();
+
+      let state = host.state();
+      assert!(state.directories.contains(&path_to_data_dir));
+      assert!(!state.files.contains_key(Path::new("/tmp/rusty-sailor/etcd/etcd")));
+      assert!(!state.files.contains_key(Path::new("/tmp/rusty-sailor/etcd/etcd.conf.yml")));
+      assert!(!state.directories.contains(Path::new("/tmp/rusty-sailor/etcd/certs")));
+      assert_eq!(state.directories.contains(Path::new("/tmp/rusty-sailor/pki")), !wipe_pki);
+    }
+  }
+}
@@ -1,9 +1,11 @@
 use std::net::IpAddr;
 
 use crate::errors::{ErrorKind, InstallError};
-use crate::components::InstallStepResult;
+use crate::components::{Component, InstallStepResult};
 use crate::install_ctx::InstallCtx;
 
+pub const GLOBAL_VALIDATION_COMPONENT_NAME: &str = "global-validation";
+
 fn _bind_address_validation(
   bind_address: IpAddr
 ) -> Result<(), InstallError> {
@@ -24,9 +26,28 @@ fn _bind_address_validation(
 
 // DO NOT! USE WILLY-WILLY-NILLY
 // ONLY IF IN-COMPONENT WILL NOT HELP
-pub fn global_validation_component(
-  mut install_ctx: InstallCtx
-) -> InstallStepResult {
-  _bind_address_validation(install_ctx.config.bind_address)?;
-  Ok(install_ctx)
+pub struct GlobalValidationComponent;
+
+impl Component for GlobalValidationComponent {
+  fn name(&self) -> &'static str {
+    GLOBAL_VALIDATION_COMPONENT_NAME
+  }
+
+  fn is_resumable(&self) -> bool {
+    false
+  }
+
+  fn validate(
+    &self,
+    install_ctx: &InstallCtx
+  ) -> Result<(), InstallError> {
+    _bind_address_validation(install_ctx.config.bind_address)
+  }
+
+  fn install(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult {
+    Ok(install_ctx)
+  }
 }
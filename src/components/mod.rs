@@ -1,46 +1,93 @@
-use log::{error, info};
-
+use crate::components::reset::ResetOptions;
 use crate::errors::InstallError;
+use crate::host::tracking::Artifact;
 use crate::install_ctx::InstallCtx;
+use crate::report::Endpoint;
 
 pub mod ca;
 pub mod etcd;
 pub mod global_validation;
+pub mod preflight;
+pub mod reset;
+pub mod runner;
 
 pub type InstallStepResult = Result<InstallCtx, InstallError>;
 
-// For some weird reason, having this function
-// written as anonymous one, will make 
-// compiler really unhappy.
-// TODO: Place somewhere else
-// TODO: Post issue
-fn _bind(
-  acc: InstallStepResult,
-  f: &Fn(InstallCtx) -> InstallStepResult
-) -> InstallStepResult {
-  match acc {
-    Ok(a) => f(a),
-    Err(e) => Err(e)
+pub trait Component {
+  // Unique, used for ordering and for --only/--skip selection
+  fn name(&self) -> &'static str;
+
+  fn depends_on(&self) -> Vec<&'static str> {
+    vec![]
   }
-}
 
-pub fn run_steps(
-  install_ctx: InstallStepResult,
-  install_components: Vec<&Fn(InstallCtx) -> InstallStepResult>
-) -> Result<(), InstallError> {
-  let status = match install_components.into_iter().fold(
-    install_ctx,
-    _bind
-  ) {
-    Ok(_) => {
-      info!("Installation has been successfully completed!");
-      Ok(())
-    }
-    Err(error) => {
-      error!("Installation has failed!");
-      error!("Error details: '{}'", error.to_string());
-      Err(error)
-    }
-  };
-  status
+  // Components, which only check the node,
+  // should be executed on every run
+  fn is_resumable(&self) -> bool {
+    true
+  }
+
+  // Whatever the step depends on, if it changes
+  // between runs - step will be executed again
+  fn inputs(
+    &self,
+    install_ctx: &InstallCtx
+  ) -> Result<serde_json::Value, InstallError> {
+    Ok(serde_json::to_value(&install_ctx.config)?)
+  }
+
+  fn validate(
+    &self,
+    _install_ctx: &InstallCtx
+  ) -> Result<(), InstallError> {
+    Ok(())
+  }
+
+  fn install(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult;
+
+  // Called instead of install, when step has been completed
+  // by one of previous runs - has to restore whatever
+  // install would have put into the context
+  fn resume(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult {
+    Ok(install_ctx)
+  }
+
+  fn verify(
+    &self,
+    _install_ctx: &InstallCtx
+  ) -> Result<(), InstallError> {
+    Ok(())
+  }
+
+  // Addresses of services, that the component provides
+  fn endpoints(
+    &self,
+    _install_ctx: &InstallCtx
+  ) -> Vec<Endpoint> {
+    vec![]
+  }
+
+  // Everything, that reset should remove - in order of creation
+  fn installed_artifacts(
+    &self,
+    _install_ctx: &InstallCtx,
+    _options: &ResetOptions
+  ) -> Vec<Artifact> {
+    vec![]
+  }
+
+  // Called by reset before installed artifacts are removed
+  fn uninstall(
+    &self,
+    install_ctx: InstallCtx,
+    _options: &ResetOptions
+  ) -> InstallStepResult {
+    Ok(install_ctx)
+  }
 }
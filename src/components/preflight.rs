@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use log::info;
+
+use crate::components::etcd::{ETCD_COMPONENT_NAME, ETCD_SYSTEMD_UNIT_NAME};
+use crate::components::reset::ResetOptions;
+use crate::components::{Component, InstallStepResult};
+use crate::components::global_validation::GLOBAL_VALIDATION_COMPONENT_NAME;
+use crate::errors::InstallError;
+use crate::host::Host;
+use crate::host::tracking::Artifact;
+use crate::install_ctx::InstallCtx;
+use crate::journal::Journal;
+use crate::preflight::network::network_preflight;
+use crate::preflight::system::{
+  system_preflight,
+  SystemPaths,
+  MODULES_LOAD_DROP_IN_PATH,
+  SYSCTL_DROP_IN_PATH
+};
+
+pub const NETWORK_PREFLIGHT_COMPONENT_NAME: &str = "network-preflight";
+pub const SYSTEM_PREFLIGHT_COMPONENT_NAME: &str = "system-preflight";
+
+pub struct NetworkPreflightComponent;
+
+impl Component for NetworkPreflightComponent {
+  fn name(&self) -> &'static str {
+    NETWORK_PREFLIGHT_COMPONENT_NAME
+  }
+
+  fn depends_on(&self) -> Vec<&'static str> {
+    vec![GLOBAL_VALIDATION_COMPONENT_NAME]
+  }
+
+  fn is_resumable(&self) -> bool {
+    false
+  }
+
+  fn validate(
+    &self,
+    install_ctx: &InstallCtx
+  ) -> Result<(), InstallError> {
+    // Network of an image is not the one of the machine,
+    // that is building it
+    if install_ctx.host.root().is_some() {
+      info!("Installing under custom root, network preflight is skipped");
+      return Ok(())
+    }
+    // Re-run or resumed installation finds etcd
+    // started by the previous one on its ports
+    let journal = Journal::load(&*install_ctx.host, &Journal::path(&install_ctx.config))?;
+    let is_etcd_installed = journal.components.contains_key(ETCD_COMPONENT_NAME)
+      && install_ctx.host.check_unit_status(ETCD_SYSTEMD_UNIT_NAME).is_ok();
+    if is_etcd_installed {
+      info!("etcd has been installed already, its ports are not checked");
+    }
+    network_preflight(&install_ctx.config, is_etcd_installed)
+  }
+
+  fn install(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult {
+    Ok(install_ctx)
+  }
+}
+
+pub struct SystemPreflightComponent {
+  pub should_fix: bool
+}
+
+impl Component for SystemPreflightComponent {
+  fn name(&self) -> &'static str {
+    SYSTEM_PREFLIGHT_COMPONENT_NAME
+  }
+
+  fn depends_on(&self) -> Vec<&'static str> {
+    vec![GLOBAL_VALIDATION_COMPONENT_NAME]
+  }
+
+  fn is_resumable(&self) -> bool {
+    false
+  }
+
+  // Fixing prerequisites changes the host,
+  // hence checks are done during install
+  fn install(
+    &self,
+    install_ctx: InstallCtx
+  ) -> InstallStepResult {
+    system_preflight(
+      &*install_ctx.host,
+      &install_ctx.config,
+      &SystemPaths::default(),
+      self.should_fix
+    )?;
+    Ok(install_ctx)
+  }
+  // Drop-ins written by --fix
+  fn installed_artifacts(
+    &self,
+    _install_ctx: &InstallCtx,
+    _options: &ResetOptions
+  ) -> Vec<Artifact> {
+    vec![
+      Artifact::File { path: PathBuf::from(MODULES_LOAD_DROP_IN_PATH) },
+      Artifact::File { path: PathBuf::from(SYSCTL_DROP_IN_PATH) }
+    ]
+  }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use log::{error, info};
+
+use crate::components::runner::{order_components, ComponentSelection};
+use crate::components::{Component, InstallStepResult};
+use crate::errors::{ErrorKind, InstallError};
+use crate::host::Host;
+use crate::host::tracking::{rollback, Artifact};
+use crate::install_ctx::InstallCtx;
+use crate::journal::Journal;
+
+#[derive(Debug, Default)]
+pub struct ResetOptions {
+  // Removes etcd data_dir, cluster state is lost
+  pub wipe_data: bool,
+  // Removes CA (and with it installation_dir, unless etcd data is kept there)
+  pub wipe_pki: bool
+}
+
+fn _is_present(
+  host: &dyn Host,
+  artifact: &Artifact
+) -> bool {
+  match artifact {
+    Artifact::Directory { path }
+      | Artifact::File { path }
+      | Artifact::ReplacedFile { path, .. } => host.path_exists(path),
+    Artifact::Unit { name } => host.is_unit_enabled(name)
+  }
+}
+
+// Installation dir keeps journal and PKI, so it can be removed
+// only together with the latter - and with etcd data, when
+// data_dir lies within it (as it does by default)
+fn _installation_dir_artifacts(
+  install_ctx: &InstallCtx,
+  options: &ResetOptions
+) -> Vec<Artifact> {
+  let installation_dir = Path::new(&install_ctx.config.installation_dir);
+  let keeps_data = !options.wipe_data
+    && Path::new(&install_ctx.config.etcd.data_dir).starts_with(installation_dir);
+  match options.wipe_pki && !keeps_data {
+    true => vec![Artifact::Directory { path: installation_dir.to_path_buf() }],
+    false => vec![Artifact::File { path: Journal::path(&install_ctx.config) }]
+  }
+}
+
+// Everything, that is present on the node and
+// will be removed by reset - in order of creation
+pub fn reset_plan(
+  install_ctx: &InstallCtx,
+  components: &[Box<dyn Component>],
+  options: &ResetOptions
+) -> Result<Vec<Artifact>, InstallError> {
+  let ordered = order_components(components, &ComponentSelection::default())?;
+  let mut artifacts = _installation_dir_artifacts(install_ctx, options);
+  for component in ordered {
+    artifacts.extend(component.installed_artifacts(install_ctx, options));
+  }
+  Ok(
+    artifacts.into_iter()
+      .filter(|x| _is_present(&*install_ctx.host, x))
+      .collect()
+  )
+}
+
+fn _reset_components(
+  install_ctx: InstallCtx,
+  components: &[Box<dyn Component>],
+  options: &ResetOptions
+) -> Result<(), InstallError> {
+  let ordered = order_components(components, &ComponentSelection::default())?;
+  let plan = reset_plan(&install_ctx, components, options)?;
+
+  let install_ctx = ordered.iter().rev().try_fold(
+    install_ctx,
+    |ctx, component| {
+      info!("[{}] Uninstalling", component.name());
+      component.uninstall(ctx, options)
+    }
+  )?;
+
+  let remaining = rollback(install_ctx.host.untracked(), &plan);
+  install_ctx.host.reload_systemd()?;
+  if !remaining.is_empty() {
+    return Err(
+      InstallError::new(
+        ErrorKind::Other,
+        format!("{} item(s) could not have been removed", remaining.len())
+      )
+    )
+  }
+  Ok(())
+}
+
+pub fn reset_components(
+  install_ctx: InstallStepResult,
+  components: &[Box<dyn Component>],
+  options: &ResetOptions
+) -> Result<(), InstallError> {
+  match install_ctx.and_then(|ctx| _reset_components(ctx, components, options)) {
+    Ok(_) => {
+      info!("Reset has been successfully completed!");
+      Ok(())
+    },
+    Err(error) => {
+      error!("Reset has failed!");
+      error!("Error details: '{}'", error);
+      Err(error)
+    }
+  }
+}
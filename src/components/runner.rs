@@ -0,0 +1,601 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+use crate::components::{Component, InstallStepResult};
+use crate::errors::{Context, ErrorKind, InstallError};
+use crate::host::Host;
+use crate::host::tracking::{rollback, Artifact};
+use crate::install_ctx::InstallCtx;
+use crate::journal::{hash_inputs, Journal, JournalEntry, StepStatus};
+use crate::progress::{self, Event};
+use crate::report::{ComponentStatus, InstallReport};
+
+// Names of components picked with --only/--skip/--force,
+// empty `only` means every component
+#[derive(Debug, Default)]
+pub struct ComponentSelection {
+  pub only: Vec<String>,
+  pub skip: Vec<String>,
+  // Executed even if journal says they are completed
+  pub force: Vec<String>
+}
+
+#[derive(Debug, Default)]
+pub struct RunOptions {
+  // Leave artifacts of a failed step on the node,
+  // instead of rolling them back
+  pub keep_on_failure: bool,
+  // Where JSON report of the run is written
+  pub report_path: Option<PathBuf>
+}
+
+fn _find_component<'a>(
+  components: &'a [Box<dyn Component>],
+  name: &str
+) -> Option<&'a dyn Component> {
+  components.iter().find(|c| c.name() == name).map(|c| c.as_ref())
+}
+
+fn _check_components(
+  components: &[Box<dyn Component>]
+) -> Result<(), InstallError> {
+  let mut names = HashSet::new();
+  for component in components.iter() {
+    if !names.insert(component.name()) {
+      return Err(
+        InstallError::new(
+          ErrorKind::Component,
+          format!("Component `{}` is defined more than once", component.name())
+        )
+      )
+    }
+  }
+  for component in components.iter() {
+    for dependency in component.depends_on() {
+      if !names.contains(dependency) {
+        return Err(
+          InstallError::new(
+            ErrorKind::Component,
+            format!(
+              "Component `{}` depends on unknown component `{}`",
+              component.name(),
+              dependency
+            )
+          )
+        )
+      }
+    }
+  }
+  Ok(())
+}
+
+fn _select_components(
+  components: &[Box<dyn Component>],
+  selection: &ComponentSelection
+) -> Result<HashSet<&'static str>, InstallError> {
+  let requested_names = selection.only.iter()
+    .chain(selection.skip.iter())
+    .chain(selection.force.iter());
+  for name in requested_names {
+    if _find_component(components, name).is_none() {
+      return Err(
+        InstallError::new(
+          ErrorKind::Component,
+          format!("There is no component named `{}`", name)
+        )
+      )
+    }
+  }
+
+  let mut selected: HashSet<&'static str> = HashSet::new();
+  if selection.only.is_empty() {
+    selected.extend(components.iter().map(|c| c.name()));
+  }
+  else {
+    // Selected components bring their dependencies along
+    let mut to_visit = selection.only.clone();
+    while let Some(name) = to_visit.pop() {
+      if let Some(component) = _find_component(components, &name) {
+        if selected.insert(component.name()) {
+          to_visit.extend(component.depends_on().iter().map(|x| x.to_string()));
+        }
+      }
+    }
+  }
+  for name in selection.skip.iter() {
+    selected.remove(name.as_str());
+  }
+  Ok(selected)
+}
+
+// Dependencies go first, otherwise the order
+// in which components were given is kept
+pub fn order_components<'a>(
+  components: &'a [Box<dyn Component>],
+  selection: &ComponentSelection
+) -> Result<Vec<&'a dyn Component>, InstallError> {
+  _check_components(components)?;
+  let selected = _select_components(components, selection)?;
+
+  let mut ordered: Vec<&'a dyn Component> = vec![];
+  let mut ordered_names: HashSet<&'static str> = HashSet::new();
+  while ordered.len() < selected.len() {
+    let next = components.iter().find(|c| {
+      selected.contains(c.name())
+        && !ordered_names.contains(c.name())
+        && c.depends_on().iter().all(
+          |d| !selected.contains(d) || ordered_names.contains(d)
+        )
+    });
+    match next {
+      Some(component) => {
+        ordered_names.insert(component.name());
+        ordered.push(component.as_ref());
+      },
+      None => {
+        let mut remaining = selected
+          .difference(&ordered_names)
+          .copied()
+          .collect::<Vec<&str>>();
+        remaining.sort();
+        return Err(
+          InstallError::new(
+            ErrorKind::Component,
+            format!("Components {:?} have cyclic dependencies", remaining)
+          )
+        )
+      }
+    }
+  }
+  Ok(ordered)
+}
+
+fn _run_component(
+  component: &dyn Component,
+  install_ctx: InstallCtx
+) -> (InstallStepResult, Duration) {
+  info!("[{}] Starting", component.name());
+  progress::emit(Event::ComponentStarted { name: component.name().to_string() });
+  let started_at = Instant::now();
+
+  let result = component.validate(&install_ctx)
+    .and_then(|_| component.install(install_ctx))
+    .and_then(|ctx| component.verify(&ctx).map(|_| ctx))
+    .with_context(|| format!("while installing component `{}`", component.name()));
+
+  let duration = started_at.elapsed();
+  match &result {
+    Ok(_) => info!(
+      "[{}] Finished in {:.2?}",
+      component.name(),
+      duration
+    ),
+    Err(_) => error!(
+      "[{}] Failed after {:.2?}",
+      component.name(),
+      duration
+    )
+  };
+  (result, duration)
+}
+
+fn _component_status(
+  result: &InstallStepResult
+) -> ComponentStatus {
+  match result {
+    Ok(_) => ComponentStatus::Completed,
+    Err(_) => ComponentStatus::Failed
+  }
+}
+
+fn _component_finished(
+  report: &mut InstallReport,
+  host: &dyn Host,
+  component: &dyn Component,
+  status: ComponentStatus,
+  duration: Duration,
+  artifacts: Vec<Artifact>
+) {
+  progress::emit(
+    Event::ComponentFinished { name: component.name().to_string(), status, duration }
+  );
+  report.add_component(host, component.name(), status, duration, artifacts);
+}
+
+// Tracks, which steps may be skipped thanks to the journal
+struct Resumption<'a> {
+  journal: Journal,
+  // Every step, that depends on one of these, has to be executed again
+  executed: HashSet<&'static str>,
+  selection: &'a ComponentSelection,
+  options: &'a RunOptions,
+  report: &'a mut InstallReport
+}
+
+impl<'a> Resumption<'a> {
+  fn can_skip(
+    &self,
+    component: &dyn Component,
+    inputs_hash: &str
+  ) -> bool {
+    !self.selection.force.iter().any(|x| x == component.name())
+      && !component.depends_on().iter().any(|x| self.executed.contains(x))
+      && self.journal.is_completed(component.name(), inputs_hash)
+  }
+}
+
+// Changes made by failed component are rolled back, unless asked to keep them
+fn _run_tracked_component(
+  component: &dyn Component,
+  install_ctx: InstallCtx,
+  resumption: &mut Resumption
+) -> (InstallStepResult, Vec<Artifact>) {
+  let host = Rc::clone(&install_ctx.host);
+  host.take_artifacts();
+  let (result, duration) = _run_component(component, install_ctx);
+  let mut artifacts = host.take_artifacts();
+  if result.is_err() && !resumption.options.keep_on_failure && !artifacts.is_empty() {
+    info!("[{}] Rolling back {} change(s)", component.name(), artifacts.len());
+    artifacts = rollback(host.untracked(), &artifacts);
+  }
+  _component_finished(
+    resumption.report,
+    host.untracked(),
+    component,
+    _component_status(&result),
+    duration,
+    artifacts.clone()
+  );
+  (result, artifacts)
+}
+
+fn _run_journaled_component(
+  component: &dyn Component,
+  install_ctx: InstallCtx,
+  resumption: &mut Resumption,
+  journal_path: &Path
+) -> InstallStepResult {
+  let host = Rc::clone(&install_ctx.host);
+  if !component.is_resumable() {
+    // Nothing to resume from, so the journal is left alone
+    return _run_tracked_component(component, install_ctx, resumption).0
+  }
+
+  let inputs_hash = hash_inputs(&component.inputs(&install_ctx)?)?;
+  if resumption.can_skip(component, &inputs_hash) {
+    info!("[{}] Already completed by previous run, skipping", component.name());
+    let artifacts = resumption.journal.components
+      .get(component.name())
+      .map_or_else(Vec::new, |entry| entry.artifacts.clone());
+    _component_finished(
+      resumption.report,
+      host.untracked(),
+      component,
+      ComponentStatus::Skipped,
+      Duration::default(),
+      artifacts
+    );
+    return component.resume(install_ctx)
+  }
+
+  resumption.executed.insert(component.name());
+  let (result, artifacts) = _run_tracked_component(component, install_ctx, resumption);
+  resumption.journal.record(
+    component.name(),
+    JournalEntry {
+      status: if result.is_ok() { StepStatus::Completed } else { StepStatus::Failed },
+      inputs_hash,
+      artifacts
+    }
+  );
+
+  let saved = resumption.journal.save(host.untracked(), journal_path);
+  if let Err(e) = &saved {
+    warn!("Unable to save journal `{}`: {}", journal_path.display(), e);
+  }
+  result.and_then(|ctx| saved.map(|_| ctx))
+}
+
+fn _run_ordered_components(
+  install_ctx: InstallCtx,
+  ordered: Vec<&dyn Component>,
+  selection: &ComponentSelection,
+  options: &RunOptions,
+  report: &mut InstallReport
+) -> InstallStepResult {
+  let journal_path = Journal::path(&install_ctx.config);
+  let mut resumption = Resumption {
+    journal: Journal::load(&*install_ctx.host, &journal_path)?,
+    executed: HashSet::new(),
+    selection,
+    options,
+    report
+  };
+  ordered.into_iter().try_fold(
+    install_ctx,
+    |ctx, component| {
+      let ctx = _run_journaled_component(component, ctx, &mut resumption, &journal_path)?;
+      resumption.report.add_endpoints(component.endpoints(&ctx));
+      Ok(ctx)
+    }
+  )
+}
+
+pub fn run_components(
+  install_ctx: InstallStepResult,
+  components: &[Box<dyn Component>],
+  selection: &ComponentSelection,
+  options: &RunOptions
+) -> Result<(), InstallError> {
+  let mut report = InstallReport::new();
+  let status = order_components(components, selection).and_then(|ordered| {
+    let names = ordered.iter().map(|c| c.name().to_string()).collect::<Vec<String>>();
+    info!("Components to install: {}", names.join(", "));
+    progress::emit(Event::RunStarted { components: names });
+    install_ctx.and_then(
+      |ctx| _run_ordered_components(ctx, ordered, selection, options, &mut report)
+    )
+  }).map(|_| ());
+  progress::emit(Event::RunFinished);
+
+  match &status {
+    Ok(_) => info!("Installation has been successfully completed!"),
+    Err(error) => {
+      error!("Installation has failed!");
+      error!("Error details: '{}'", error);
+    }
+  };
+
+  report.finish(&status);
+  let saved = match &options.report_path {
+    Some(path) => report.save(path),
+    None => Ok(())
+  };
+  // Failure of installation itself matters more
+  match (status, saved) {
+    (Err(error), Err(e)) => {
+      warn!("Unable to save report: {}", e);
+      Err(error)
+    },
+    (status, saved) => status.and(saved)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::sources::ConfigSources;
+  use crate::host::Host;
+  use crate::host::fake::FakeHost;
+  use super::*;
+
+  struct Dummy {
+    name: &'static str,
+    depends_on: Vec<&'static str>,
+    fails: bool,
+    resumable: bool
+  }
+
+  impl Component for Dummy {
+    fn name(&self) -> &'static str {
+      self.name
+    }
+
+    fn depends_on(&self) -> Vec<&'static str> {
+      self.depends_on.clone()
+    }
+
+    fn is_resumable(&self) -> bool {
+      self.resumable
+    }
+
+    fn install(
+      &self,
+      install_ctx: InstallCtx
+    ) -> InstallStepResult {
+      install_ctx.host.run_command(self.name, &[])?;
+      install_ctx.host.create_dir_all(&Path::new("/opt").join(self.name))?;
+      if self.fails {
+        return Err(InstallError::new_from_str(ErrorKind::Other, "Dummy has failed"))
+      }
+      Ok(install_ctx)
+    }
+  }
+
+  fn _dummy(
+    name: &'static str,
+    depends_on: Vec<&'static str>
+  ) -> Box<dyn Component> {
+    Box::new(Dummy { name, depends_on, fails: false, resumable: true })
+  }
+
+  fn _ordered_names(
+    components: &[Box<dyn Component>],
+    only: Vec<&str>,
+    skip: Vec<&str>
+  ) -> Result<Vec<&'static str>, InstallError> {
+    let selection = ComponentSelection {
+      only: only.into_iter().map(String::from).collect(),
+      skip: skip.into_iter().map(String::from).collect(),
+      force: vec![]
+    };
+    order_components(components, &selection).map(
+      |ordered| ordered.iter().map(|c| c.name()).collect()
+    )
+  }
+
+  fn _components() -> Vec<Box<dyn Component>> {
+    vec![
+      _dummy("etcd", vec!["ca", "preflight"]),
+      _dummy("preflight", vec![]),
+      _dummy("ca", vec![]),
+      _dummy("containerd", vec!["preflight"])
+    ]
+  }
+
+  #[test]
+  fn test_dependencies_go_first() {
+    assert_eq!(
+      _ordered_names(&_components(), vec![], vec![]).unwrap(),
+      vec!["preflight", "ca", "etcd", "containerd"]
+    );
+  }
+
+  #[test]
+  fn test_only_brings_dependencies_along() {
+    assert_eq!(
+      _ordered_names(&_components(), vec!["etcd"], vec![]).unwrap(),
+      vec!["preflight", "ca", "etcd"]
+    );
+    assert_eq!(
+      _ordered_names(&_components(), vec!["etcd"], vec!["preflight"]).unwrap(),
+      vec!["ca", "etcd"]
+    );
+  }
+
+  #[test]
+  fn test_invalid_selection_and_cycles() {
+    assert!(_ordered_names(&_components(), vec!["kubelet"], vec![]).is_err());
+
+    let cyclic = vec![
+      _dummy("a", vec!["b"]),
+      _dummy("b", vec!["a"]),
+      _dummy("c", vec![])
+    ];
+    let error = _ordered_names(&cyclic, vec![], vec![]).unwrap_err();
+    assert!(error.msg.contains("[\"a\", \"b\"]"));
+
+    let unknown_dependency = vec![_dummy("a", vec!["z"])];
+    assert!(_ordered_names(&unknown_dependency, vec![], vec![]).is_err());
+  }
+
+  fn _executed_components(
+    host: &FakeHost,
+    components: &[Box<dyn Component>],
+    selection: &ComponentSelection
+  ) -> Result<Vec<String>, InstallError> {
+    host.state_mut().commands.clear();
+    let ctx = InstallCtx::new(&ConfigSources::default(), Box::new(host.clone()));
+    let result = run_components(ctx, components, selection, &RunOptions::default());
+    let executed = host.state().commands.iter().map(|x| x[0].clone()).collect();
+    result.map(|_| executed)
+  }
+
+  #[test]
+  fn test_install_is_resumed_from_failed_component() {
+    let host = FakeHost::new();
+    let failing = vec![
+      _dummy("ca", vec![]),
+      Box::new(Dummy { name: "etcd", depends_on: vec!["ca"], fails: true, resumable: true }) as Box<dyn Component>,
+      _dummy("kubelet", vec!["etcd"])
+    ];
+    assert!(_executed_components(&host, &failing, &ComponentSelection::default()).is_err());
+    assert!(host.path_exists(Path::new("/opt/ca")));
+    assert!(!host.path_exists(Path::new("/opt/etcd")));
+
+    let components = vec![
+      _dummy("ca", vec![]),
+      _dummy("etcd", vec!["ca"]),
+      _dummy("kubelet", vec!["etcd"])
+    ];
+    assert_eq!(
+      _executed_components(&host, &components, &ComponentSelection::default()).unwrap(),
+      vec!["etcd", "kubelet"]
+    );
+    assert!(
+      _executed_components(&host, &components, &ComponentSelection::default())
+        .unwrap()
+        .is_empty()
+    );
+
+    // Forced component takes its dependents along
+    let selection = ComponentSelection {
+      force: vec!["etcd".to_string()],
+      ..ComponentSelection::default()
+    };
+    assert_eq!(
+      _executed_components(&host, &components, &selection).unwrap(),
+      vec!["etcd", "kubelet"]
+    );
+
+    // Changed inputs invalidate completed steps
+    let journal_path = {
+      let ctx = InstallCtx::new(&ConfigSources::default(), Box::new(host.clone())).unwrap();
+      Journal::path(&ctx.config)
+    };
+    let mut journal = Journal::load(&host, &journal_path).unwrap();
+    journal.components.get_mut("ca").unwrap().inputs_hash = "outdated".to_string();
+    journal.save(&host, &journal_path).unwrap();
+    assert_eq!(
+      _executed_components(&host, &components, &ComponentSelection::default()).unwrap(),
+      vec!["ca", "etcd", "kubelet"]
+    );
+  }
+
+  #[test]
+  fn test_keep_on_failure() {
+    let host = FakeHost::new();
+    let failing: Vec<Box<dyn Component>> = vec![
+      Box::new(Dummy { name: "etcd", depends_on: vec![], fails: true, resumable: true })
+    ];
+    let result = run_components(
+      InstallCtx::new(&ConfigSources::default(), Box::new(host.clone())),
+      &failing,
+      &ComponentSelection::default(),
+      &RunOptions { keep_on_failure: true, ..Default::default() }
+    );
+    assert!(result.is_err());
+    assert!(host.path_exists(Path::new("/opt/etcd")));
+  }
+
+  #[test]
+  fn test_changes_of_failed_check_are_rolled_back() {
+    let host = FakeHost::new();
+    let failing: Vec<Box<dyn Component>> = vec![
+      Box::new(Dummy { name: "preflight", depends_on: vec![], fails: true, resumable: false })
+    ];
+    assert!(_executed_components(&host, &failing, &ComponentSelection::default()).is_err());
+    assert!(!host.path_exists(Path::new("/opt/preflight")));
+  }
+
+  #[test]
+  fn test_report_is_written() {
+    let host = FakeHost::new();
+    let report_dir = tempfile::TempDir::new().unwrap();
+    let report_path = report_dir.path().join("report.json");
+    let components = vec![
+      _dummy("ca", vec![]),
+      Box::new(Dummy { name: "etcd", depends_on: vec!["ca"], fails: true, resumable: true }) as Box<dyn Component>
+    ];
+    let result = run_components(
+      InstallCtx::new(&ConfigSources::default(), Box::new(host.clone())),
+      &components,
+      &ComponentSelection::default(),
+      &RunOptions { report_path: Some(report_path.clone()), ..Default::default() }
+    );
+    assert!(result.is_err());
+
+    let report: serde_json::Value = serde_json::from_slice(
+      &std::fs::read(&report_path).unwrap()
+    ).unwrap();
+    assert_eq!(report["succeeded"], false);
+    assert_eq!(report["components"][0]["name"], "ca");
+    assert_eq!(report["components"][0]["status"], "completed");
+    assert!(
+      report["components"][0]["artifacts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|x| x["kind"] == "directory" && x["path"] == "/opt/ca")
+    );
+    assert_eq!(report["components"][1]["status"], "failed");
+    assert!(report["components"][1]["artifacts"].as_array().unwrap().is_empty());
+    assert_eq!(
+      report["error"]["message"],
+      "Dummy has failed, while installing component `etcd`"
+    );
+  }
+}
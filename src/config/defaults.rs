@@ -0,0 +1,53 @@
+use askama::Template;
+
+use crate::config::Settings;
+use crate::errors::InstallError;
+
+#[derive(Template)]
+#[template(path = "config/defaults.toml", escape = "none")]
+struct DefaultsTemplate<'a> {
+  settings: &'a Settings
+}
+
+mod filters {
+  use std::fmt;
+  use std::time::Duration;
+
+  use serde::Serialize;
+
+  // Renders value as TOML literal, with strings quoted and escaped
+  pub fn toml<T: Serialize>(
+    value: &T
+  ) -> askama::Result<String> {
+    toml::Value::try_from(value)
+      .map(|x| x.to_string())
+      .map_err(|_| askama::Error::Fmt(fmt::Error))
+  }
+
+  pub fn duration(
+    value: &Duration
+  ) -> askama::Result<String> {
+    toml(&humantime::format_duration(*value).to_string())
+  }
+}
+
+// Full default configuration, with every key documented
+pub fn defaults_toml() -> Result<String, InstallError> {
+  Ok(DefaultsTemplate { settings: &Settings::default() }.render()?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_defaults_can_be_read_back() {
+    let rendered = defaults_toml().unwrap();
+    let settings: Settings = toml::from_str(&rendered).unwrap();
+    let defaults = Settings::default();
+    assert_eq!(settings.hostname, defaults.hostname);
+    assert_eq!(settings.etcd.peer_connect_timeout, defaults.etcd.peer_connect_timeout);
+    assert_eq!(settings.pki.organizational_unit, defaults.pki.organizational_unit);
+    assert!(rendered.contains("# [[etcd.other_nodes]]"));
+  }
+}
@@ -0,0 +1,414 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::config::API_VERSION;
+use crate::config::schema::settings_schema;
+use crate::errors::{ErrorKind, InstallError};
+
+pub const API_VERSION_KEY: &str = "api_version";
+// Files from before versioning have no api_version at all
+const LEGACY_VERSION_NAME: &str = "unversioned";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+  Json,
+  Toml,
+  Yaml
+}
+
+impl ConfigFormat {
+  pub fn from_path(
+    path: &Path
+  ) -> Result<Self, InstallError> {
+    match path.extension().and_then(|x| x.to_str()) {
+      Some("json") => Ok(ConfigFormat::Json),
+      Some("toml") => Ok(ConfigFormat::Toml),
+      Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+      _ => Err(
+        InstallError::new(
+          ErrorKind::Config,
+          format!("`{}` is neither toml, yaml nor json file", path.display())
+        )
+      )
+    }
+  }
+
+  // For contents without a file name, e.g. from stdin
+  pub fn guess(
+    contents: &str
+  ) -> Self {
+    if contents.parse::<toml::Value>().is_ok() {
+      ConfigFormat::Toml
+    }
+    else if contents.trim_start().starts_with('{') {
+      ConfigFormat::Json
+    }
+    else {
+      ConfigFormat::Yaml
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Change {
+  SetVersion(&'static str),
+  Remove(Vec<String>)
+}
+
+// Upgrades config from `from` version (None for unversioned)
+struct Migration {
+  from: Option<&'static str>,
+  to: &'static str,
+  changes: fn(&Value) -> Vec<Change>
+}
+
+const MIGRATIONS: [Migration; 1] = [
+  // Unversioned files were read leniently, so they may
+  // contain keys, which are rejected now
+  Migration { from: None, to: API_VERSION, changes: _remove_unknown_keys }
+];
+
+fn _resolve<'a>(
+  schema: &'a Value,
+  root: &'a Value
+) -> &'a Value {
+  if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+    let pointer = reference.trim_start_matches('#');
+    return _resolve(root.pointer(pointer).unwrap_or(&Value::Null), root)
+  }
+  match schema.get("allOf").and_then(Value::as_array) {
+    Some(all_of) if all_of.len() == 1 => _resolve(&all_of[0], root),
+    _ => schema
+  }
+}
+
+// Properties of the object itself, or of each of its variants,
+// e.g. of the tagged `artifacts` enum
+fn _variant_properties<'a>(
+  schema: &'a Value,
+  root: &'a Value
+) -> Vec<&'a serde_json::Map<String, Value>> {
+  let variants = schema.get("oneOf")
+    .or_else(|| schema.get("anyOf"))
+    .and_then(Value::as_array);
+  match variants {
+    Some(variants) => variants.iter()
+      .flat_map(|x| _variant_properties(_resolve(x, root), root))
+      .collect(),
+    None => schema.get("properties").and_then(Value::as_object).into_iter().collect()
+  }
+}
+
+fn _collect_unknown_keys(
+  value: &Value,
+  schema: &Value,
+  root: &Value,
+  path: &mut Vec<String>,
+  unknown: &mut Vec<Vec<String>>
+) {
+  let schema = _resolve(schema, root);
+  match value {
+    Value::Object(map) => {
+      let variants = _variant_properties(schema, root);
+      if variants.is_empty() {
+        return
+      }
+      for (key, value) in map {
+        path.push(key.clone());
+        match variants.iter().find_map(|properties| properties.get(key)) {
+          Some(property) => _collect_unknown_keys(value, property, root, path, unknown),
+          None => unknown.push(path.clone())
+        }
+        path.pop();
+      }
+    },
+    Value::Array(items) => {
+      let item_schema = match schema.get("items") {
+        Some(item_schema) => item_schema,
+        None => return
+      };
+      for (i, item) in items.iter().enumerate() {
+        path.push(i.to_string());
+        _collect_unknown_keys(item, item_schema, root, path, unknown);
+        path.pop();
+      }
+    },
+    _ => {}
+  }
+}
+
+fn _remove_unknown_keys(
+  config: &Value
+) -> Vec<Change> {
+  let schema = serde_json::to_value(settings_schema()).unwrap_or(Value::Null);
+  let mut unknown = vec![];
+  _collect_unknown_keys(config, &schema, &schema, &mut vec![], &mut unknown);
+  unknown.into_iter().map(Change::Remove).collect()
+}
+
+// Dotted key, e.g. `etcd.listen_client_port`, names one of the settings
+pub fn is_known_key(
+  key: &str
+) -> bool {
+  let schema = serde_json::to_value(settings_schema()).unwrap_or(Value::Null);
+  let config = key.rsplit('.').fold(
+    Value::Null,
+    |value, part| Value::Object(std::iter::once((part.to_string(), value)).collect())
+  );
+  let mut unknown = vec![];
+  _collect_unknown_keys(&config, &schema, &schema, &mut vec![], &mut unknown);
+  unknown.is_empty()
+}
+
+fn _describe(
+  change: &Change
+) -> String {
+  match change {
+    Change::SetVersion(version) => format!("set {} to `{}`", API_VERSION_KEY, version),
+    Change::Remove(path) => format!("removed unknown key `{}`", path.join("."))
+  }
+}
+
+fn _apply_to_value(
+  config: &mut Value,
+  change: &Change
+) {
+  match change {
+    Change::SetVersion(version) => {
+      if let Value::Object(map) = config {
+        map.insert(API_VERSION_KEY.to_string(), Value::String(version.to_string()));
+      }
+    },
+    Change::Remove(path) => {
+      let (key, parents) = match path.split_last() {
+        Some(x) => x,
+        None => return
+      };
+      let pointer = parents.iter().map(|x| format!("/{}", x)).collect::<String>();
+      if let Some(Value::Object(map)) = config.pointer_mut(&pointer) {
+        map.remove(key);
+      }
+    }
+  }
+}
+
+fn _apply_to_document(
+  document: &mut toml_edit::Document,
+  change: &Change
+) {
+  match change {
+    Change::SetVersion(version) => {
+      let is_new = !document.contains_key(API_VERSION_KEY);
+      document[API_VERSION_KEY] = toml_edit::value(*version);
+      if !is_new {
+        return
+      }
+      // Stable sort, which only moves api_version to the top
+      document.sort_values_by(
+        |x, _, y, _| (x.get() != API_VERSION_KEY).cmp(&(y.get() != API_VERSION_KEY))
+      );
+      // Leading comment of the file stays above api_version
+      let previous_first = document.iter().nth(1).map(|(key, _)| key.to_string());
+      let leading_comment = previous_first
+        .and_then(|key| document.key_decor_mut(&key))
+        .and_then(|decor| {
+          let prefix = decor.prefix().cloned();
+          decor.set_prefix("");
+          prefix
+        });
+      if let (Some(comment), Some(decor)) = (leading_comment, document.key_decor_mut(API_VERSION_KEY)) {
+        decor.set_prefix(comment);
+      }
+    },
+    Change::Remove(path) => {
+      let (key, parents) = match path.split_last() {
+        Some(x) => x,
+        None => return
+      };
+      let mut item = document.as_item_mut();
+      for parent in parents {
+        let next = match parent.parse::<usize>() {
+          Ok(i) => item.get_mut(i),
+          Err(_) => item.get_mut(parent.as_str())
+        };
+        item = match next {
+          Some(next) => next,
+          None => return
+        };
+      }
+      if let Some(table) = item.as_table_like_mut() {
+        table.remove(key);
+      }
+    }
+  }
+}
+
+fn _parse(
+  contents: &str,
+  format: ConfigFormat
+) -> Result<Value, InstallError> {
+  let parsed = match format {
+    ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+    ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+    ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string())
+  };
+  parsed.map_err(|e|
+    InstallError::new(ErrorKind::Config, format!("Configuration could not be parsed ({})", e))
+  )
+}
+
+// Changes, which bring config up to the current api_version
+fn _plan(
+  config: &Value
+) -> Result<Vec<Change>, InstallError> {
+  let mut version = match config.get(API_VERSION_KEY) {
+    None => None,
+    Some(Value::String(version)) => Some(version.clone()),
+    Some(other) => return Err(
+      InstallError::new(
+        ErrorKind::Config,
+        format!("{} has to be a string, got `{}`", API_VERSION_KEY, other)
+      )
+    )
+  };
+
+  let mut config = config.clone();
+  let mut changes = vec![];
+  while version.as_deref() != Some(API_VERSION) {
+    let migration = MIGRATIONS
+      .iter()
+      .find(|x| x.from == version.as_deref())
+      .ok_or_else(|| InstallError::new(
+        ErrorKind::Config,
+        format!(
+          "There is no migration from {} `{}`",
+          API_VERSION_KEY,
+          version.as_deref().unwrap_or(LEGACY_VERSION_NAME)
+        )
+      ))?;
+    let mut step = (migration.changes)(&config);
+    step.push(Change::SetVersion(migration.to));
+    for change in step.iter() {
+      _apply_to_value(&mut config, change);
+    }
+    changes.extend(step);
+    version = Some(migration.to.to_string());
+  }
+  Ok(changes)
+}
+
+// Returns migrated contents and description of every change.
+// Comments are kept for toml, other formats are written anew
+pub fn migrate(
+  contents: &str,
+  format: ConfigFormat
+) -> Result<(String, Vec<String>), InstallError> {
+  let mut config = _parse(contents, format)?;
+  let changes = _plan(&config)?;
+  let notes = changes.iter().map(_describe).collect();
+  if changes.is_empty() {
+    return Ok((contents.to_string(), notes))
+  }
+
+  let migrated = match format {
+    ConfigFormat::Toml => {
+      let mut document = contents.parse::<toml_edit::Document>().map_err(|e|
+        InstallError::new(ErrorKind::Config, format!("Configuration could not be parsed ({})", e))
+      )?;
+      for change in changes.iter() {
+        _apply_to_document(&mut document, change);
+      }
+      document.to_string()
+    },
+    ConfigFormat::Json | ConfigFormat::Yaml => {
+      for change in changes.iter() {
+        _apply_to_value(&mut config, change);
+      }
+      match format {
+        ConfigFormat::Json => format!("{}\n", serde_json::to_string_pretty(&config)?),
+        _ => serde_yaml::to_string(&config).map_err(|e|
+          InstallError::new(ErrorKind::Serialization, e.to_string())
+        )?
+      }
+    }
+  };
+  Ok((migrated, notes))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LEGACY_TOML: &str = "# Node of the staging cluster
+hostname = \"yacht\"
+legacy_flag = true
+
+[etcd]
+# Kept on a separate disk
+data_dir = \"/data/etcd\"
+snapshot_count = 10000
+
+[[etcd.other_nodes]]
+name = \"schooner\"
+peer_url = \"https://10.0.0.2:2380\"
+client_url = \"https://10.0.0.2:2379\"
+";
+
+  #[test]
+  fn test_unversioned_toml_is_migrated_with_comments() {
+    let (migrated, notes) = migrate(LEGACY_TOML, ConfigFormat::Toml).unwrap();
+    assert_eq!(
+      notes,
+      vec![
+        "removed unknown key `etcd.other_nodes.0.client_url`",
+        "removed unknown key `etcd.snapshot_count`",
+        "removed unknown key `legacy_flag`",
+        "set api_version to `rusty-sailor/v1`"
+      ]
+    );
+    assert!(migrated.contains("# Node of the staging cluster"));
+    assert!(migrated.contains("# Kept on a separate disk"));
+    assert!(migrated.starts_with(
+      "# Node of the staging cluster\napi_version = \"rusty-sailor/v1\"\nhostname = \"yacht\""
+    ));
+    assert!(!migrated.contains("snapshot_count"));
+    assert!(!migrated.contains("client_url"));
+
+    let settings: crate::config::Settings = toml::from_str(&migrated).unwrap();
+    assert_eq!(settings.etcd.data_dir, "/data/etcd");
+
+    let (again, notes) = migrate(&migrated, ConfigFormat::Toml).unwrap();
+    assert_eq!(again, migrated);
+    assert!(notes.is_empty());
+  }
+
+  #[test]
+  fn test_json_and_yaml_are_migrated() {
+    let (migrated, _) = migrate(
+      r#"{"hostname": "yacht", "legacy_flag": true}"#,
+      ConfigFormat::Json
+    ).unwrap();
+    let migrated: Value = serde_json::from_str(&migrated).unwrap();
+    assert_eq!(migrated["api_version"], API_VERSION);
+    assert!(migrated.get("legacy_flag").is_none());
+
+    let (migrated, _) = migrate("hostname: yacht\nlegacy_flag: true\n", ConfigFormat::Yaml).unwrap();
+    assert!(migrated.contains("api_version: rusty-sailor/v1"));
+    assert!(!migrated.contains("legacy_flag"));
+  }
+
+  #[test]
+  fn test_unknown_version_is_rejected() {
+    assert!(migrate("api_version = \"rusty-sailor/v9\"", ConfigFormat::Toml).is_err());
+  }
+
+  #[test]
+  fn test_keys_of_enum_variants_are_known() {
+    assert!(is_known_key("etcd.listen_client_port"));
+    assert!(is_known_key("artifacts.source"));
+    assert!(is_known_key("artifacts.manifest_sha256"));
+    assert!(!is_known_key("artifacts.pth"));
+    assert!(!is_known_key("etcd.listen_port"));
+  }
+}
@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use config::ConfigError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::config::secret::Secret;
+use crate::config::sources::ConfigSources;
+use crate::errors::InstallError;
+use crate::net::{guess_node_hostname, guess_node_ip};
+
+pub mod defaults;
+pub mod migration;
+pub mod schema;
+pub mod secret;
+pub mod sources;
+pub mod validation;
+
+pub const API_VERSION: &str = "rusty-sailor/v1";
+const LOG_FILE_DEFAULT_PATH: &str = "logs/rusty-sailor.log";
+
+// Where vendored archives are taken from. Manifest of directory
+// or bundle is pinned by its sha256 (hex encoded), as digests
+// of archives are only as trustworthy as the manifest listing them
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(tag = "source", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ArtifactSource {
+  // Archives embedded in the binary
+  #[default]
+  Embedded,
+  // Directory with manifest.toml and archives it lists
+  Directory { path: String, manifest_sha256: String },
+  // Tar (optionally gzipped) with manifest.toml and archives it lists
+  Bundle { path: String, manifest_sha256: String }
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaSettings {
+  pub common_name: String,
+  pub expiry_in_days: u32,
+  // Decrypts custom CA private key and encrypts the saved one
+  pub key_passphrase: Option<Secret>
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EtcdNode {
+  pub name: String,
+  pub peer_url: Url
+}
+
+impl EtcdNode {
+  // Url keeps trailing slash, which etcd
+  // would treat as a different peer url
+  pub fn peer_address(&self) -> String {
+    format!(
+      "{}://{}:{}",
+      self.peer_url.scheme(),
+      self.peer_url.host_str().unwrap_or_default(),
+      self.peer_url.port_or_known_default().unwrap_or_default()
+    )
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterState {
+  New,
+  Existing
+}
+
+impl fmt::Display for ClusterState {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ClusterState::New => write!(f, "new"),
+      ClusterState::Existing => write!(f, "existing")
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EtcdSettings {
+  pub data_dir: String,
+  // Any version carried by artifacts source, if not set
+  pub version: Option<String>,
+  pub initial_cluster_state: ClusterState,
+  pub listen_peer_port: u16,
+  pub listen_client_port: u16,
+  // Human readable, e.g. "500ms" or "3s"
+  #[serde(with = "humantime_serde")]
+  #[schemars(with = "String")]
+  pub peer_connect_timeout: Duration,
+  pub other_nodes: Option<Vec<EtcdNode>>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Off,
+  Error,
+  Warn,
+  Info,
+  Debug,
+  Trace
+}
+
+impl LogLevel {
+  pub fn to_level_filter(self) -> log::LevelFilter {
+    match self {
+      LogLevel::Off => log::LevelFilter::Off,
+      LogLevel::Error => log::LevelFilter::Error,
+      LogLevel::Warn => log::LevelFilter::Warn,
+      LogLevel::Info => log::LevelFilter::Info,
+      LogLevel::Debug => log::LevelFilter::Debug,
+      LogLevel::Trace => log::LevelFilter::Trace
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+  Text,
+  // One JSON object per line
+  Json
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFileMode {
+  Append,
+  // Previous logs are kept as <file>.1, <file>.2, ...
+  Rotate
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+  // Defaults to logs/rusty-sailor.log under installation_dir
+  pub file: Option<String>,
+  pub file_mode: LogFileMode,
+  // Number of previous logs kept, when rotating
+  pub keep_files: u32,
+  pub format: LogFormat,
+  // Level of the log file, raised to trace by `debug = true`
+  pub level: LogLevel,
+  // Overrides level for given modules, e.g. "rusty_sailor::components::etcd"
+  pub modules: BTreeMap<String, LogLevel>
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PkiSettings {
+  // Shared x509 Attributes
+  pub country_name: String,
+  pub locality: String,
+  pub organization: String,
+  pub organizational_unit: String,
+  pub state: String,
+  pub email_address: String, 
+
+  pub rsa_size: u32,
+  pub ca: CaSettings
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+  // Files without it are read as the current version with a warning,
+  // older ones have to go through `config migrate`
+  pub api_version: String,
+  pub artifacts: ArtifactSource,
+  pub bind_address: IpAddr,
+  pub debug: bool,  
+  pub etcd: EtcdSettings,
+  pub hostname: String,
+  pub installation_dir: String,
+  pub logging: LoggingSettings,
+  pub pki: PkiSettings
+}
+
+impl Settings {
+  pub fn new(sources: &ConfigSources) -> Result<Self, ConfigError> {
+    sources.load()
+  }
+
+  pub fn validate(&self) -> Result<(), InstallError> {
+    validation::validate(self)
+  }
+
+  pub fn log_file_path(&self) -> PathBuf {
+    self.logging.file.as_ref().map_or_else(
+      || PathBuf::from(&self.installation_dir).join(LOG_FILE_DEFAULT_PATH),
+      PathBuf::from
+    )
+  }
+
+  // Going through toml::Value puts plain values
+  // before tables, as TOML requires
+  pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+    toml::to_string(&toml::Value::try_from(self)?)
+  }
+}
+
+impl Default for CaSettings {
+  fn default() -> Self {
+    CaSettings {
+      common_name: "rusty-sailor-ca".to_string(),
+      expiry_in_days: 3650,
+      key_passphrase: None
+    }
+  }
+}
+
+impl Default for EtcdSettings {
+  fn default() -> Self {
+    EtcdSettings {
+      data_dir: "/tmp/rusty-sailor/etcd/data".to_string(),
+      version: None,
+      initial_cluster_state: ClusterState::New,
+      listen_client_port: 2379,
+      listen_peer_port: 2380,
+      peer_connect_timeout: Duration::from_secs(3),
+      other_nodes: None
+    }
+  }
+}
+
+impl Default for LoggingSettings {
+  fn default() -> Self {
+    LoggingSettings {
+      file: None,
+      file_mode: LogFileMode::Rotate,
+      keep_files: 5,
+      format: LogFormat::Text,
+      level: LogLevel::Info,
+      modules: BTreeMap::new()
+    }
+  }
+}
+
+impl Default for PkiSettings {
+  fn default() -> Self {
+    PkiSettings {
+      country_name: "PL".to_string(),
+      locality: "Gdansk".to_string(),
+      organization: "Rusty sailors ltd.".to_string(),
+      organizational_unit: "R&D".to_string(),
+      state: "Pomorskie".to_string(),
+      email_address: "rust-sailor@k8s.eu".to_string(),
+
+      rsa_size: 4096,
+      ca: CaSettings::default()
+    }
+  }
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      api_version: API_VERSION.to_string(),
+      artifacts: ArtifactSource::default(),
+      bind_address: guess_node_ip().unwrap_or(
+        IpAddr::V4(Ipv4Addr::new(127,0,0,1))
+      ),
+      debug: false,
+      etcd: EtcdSettings::default(),
+      hostname: guess_node_hostname().unwrap_or(
+        "localhost".to_string()
+      ),
+      installation_dir: "/tmp/rusty-sailor".to_string(),
+      logging: LoggingSettings::default(),
+      pki: PkiSettings::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_peer_address() {
+    let node = |url: &str| EtcdNode {
+      name: "schooner".to_string(),
+      peer_url: Url::parse(url).unwrap()
+    };
+    assert_eq!(node("https://10.0.0.2:2380").peer_address(), "https://10.0.0.2:2380");
+    assert_eq!(node("https://10.0.0.2:2380/").peer_address(), "https://10.0.0.2:2380");
+    assert_eq!(node("https://[fd00::2]:2380").peer_address(), "https://[fd00::2]:2380");
+    assert_eq!(node("https://schooner").peer_address(), "https://schooner:443");
+  }
+
+  #[test]
+  fn test_sections_can_be_partial() {
+    let settings: Settings = toml::from_str(
+      "[etcd]\nlisten_peer_port = 2390\npeer_connect_timeout = \"500ms\"\n[pki.ca]\nexpiry_in_days = 30"
+    ).unwrap();
+    assert_eq!(settings.etcd.listen_peer_port, 2390);
+    assert_eq!(settings.etcd.listen_client_port, 2379);
+    assert_eq!(settings.etcd.peer_connect_timeout, Duration::from_millis(500));
+    assert_eq!(settings.pki.ca.expiry_in_days, 30);
+    assert_eq!(settings.pki.ca.common_name, "rusty-sailor-ca");
+  }
+
+  #[test]
+  fn test_artifact_source() {
+    let settings: Settings = toml::from_str(
+      "[artifacts]\nsource = \"bundle\"\npath = \"/srv/rusty-sailor/bundle.tar\"\nmanifest_sha256 = \"c38c\""
+    ).unwrap();
+    assert_eq!(
+      settings.artifacts,
+      ArtifactSource::Bundle {
+        path: "/srv/rusty-sailor/bundle.tar".to_string(),
+        manifest_sha256: "c38c".to_string()
+      }
+    );
+    assert_eq!(Settings::default().artifacts, ArtifactSource::Embedded);
+    assert!(toml::from_str::<Settings>("[artifacts]\nsource = \"bundle\"").is_err());
+    assert!(
+      toml::from_str::<Settings>(
+        "[artifacts]\nsource = \"bundle\"\npath = \"/srv/rusty-sailor/bundle.tar\""
+      ).is_err()
+    );
+  }
+
+  #[test]
+  fn test_unknown_keys_are_rejected() {
+    assert!(toml::from_str::<Settings>("[etcd]\nlisten_port = 2379").is_err());
+    assert!(toml::from_str::<Settings>("[[etcd.other_nodes]]\nname = \"schooner\"\npeer_url = \"https://10.0.0.2:2380\"\nclient_url = \"https://10.0.0.2:2379\"").is_err());
+  }
+}
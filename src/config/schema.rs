@@ -0,0 +1,39 @@
+use schemars::schema::{RootSchema, Schema};
+use schemars::schema_for;
+
+use crate::config::Settings;
+use crate::errors::InstallError;
+
+// Guessed on every node, so there is no default to publish
+const GUESSED_FIELDS: [&str; 2] = ["bind_address", "hostname"];
+
+pub fn settings_schema() -> RootSchema {
+  let mut schema = schema_for!(Settings);
+  let properties = &mut schema.schema.object().properties;
+  for field in GUESSED_FIELDS.iter() {
+    if let Some(Schema::Object(property)) = properties.get_mut(*field) {
+      property.metadata().default = None;
+    }
+  }
+  schema
+}
+
+// JSON Schema of the configuration file, published
+// as schema/settings.schema.json
+pub fn settings_schema_json() -> Result<String, InstallError> {
+  Ok(serde_json::to_string_pretty(&settings_schema())?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_published_schema_is_up_to_date() {
+    assert_eq!(
+      settings_schema_json().unwrap().trim(),
+      include_str!("../../schema/settings.schema.json").trim(),
+      "regenerate it with `rusty-sailor config schema > schema/settings.schema.json`"
+    );
+  }
+}
@@ -0,0 +1,153 @@
+use std::env;
+use std::fmt;
+use std::fs;
+
+use openssl::sha::Sha256;
+use schemars::JsonSchema;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::errors::{ErrorKind, InstallError};
+
+const FILE_PREFIX: &str = "file:";
+const ENV_PREFIX: &str = "env:";
+const REDACTED: &str = "<redacted>";
+
+// Sensitive value, given in config either as `file:/path`,
+// `env:VAR` or (discouraged) plain text. It is resolved at load
+// time and never shown: Debug, Display and Serialize print
+// the reference it came from, or <redacted> for plain text
+#[derive(Clone)]
+pub struct Secret {
+  value: String,
+  reference: Option<String>
+}
+
+impl Secret {
+  pub fn resolve(
+    raw: &str
+  ) -> Result<Self, InstallError> {
+    let value = if let Some(path) = raw.strip_prefix(FILE_PREFIX) {
+      fs::read_to_string(path).map_err(|e|
+        InstallError::with_source(
+          ErrorKind::Config,
+          format!("Secret file `{}` could not be read", path),
+          e
+        )
+      )?.trim_end_matches(&['\n', '\r'][..]).to_string()
+    }
+    else if let Some(name) = raw.strip_prefix(ENV_PREFIX) {
+      env::var(name).map_err(|e|
+        InstallError::with_source(
+          ErrorKind::Config,
+          format!("Secret environment variable `{}` could not be read", name),
+          e
+        )
+      )?
+    }
+    else {
+      return Ok(Secret { value: raw.to_string(), reference: None })
+    };
+    Ok(Secret { value, reference: Some(raw.to_string()) })
+  }
+
+  pub fn expose(&self) -> &str {
+    &self.value
+  }
+
+  // Changes along with the value, so that it can be compared
+  // (e.g. in journal inputs) without the value being stored
+  pub fn fingerprint(
+    &self,
+    salt: &str
+  ) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(self.value.as_bytes());
+    hasher.finish().iter().map(|x| format!("{:02x}", x)).collect()
+  }
+
+  fn _shown(&self) -> &str {
+    self.reference.as_deref().unwrap_or(REDACTED)
+  }
+}
+
+impl fmt::Debug for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Secret({})", self._shown())
+  }
+}
+
+impl fmt::Display for Secret {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self._shown())
+  }
+}
+
+impl Serialize for Secret {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self._shown())
+  }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    Secret::resolve(&raw).map_err(D::Error::custom)
+  }
+}
+
+impl JsonSchema for Secret {
+  fn schema_name() -> String {
+    "Secret".to_string()
+  }
+
+  fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+    String::json_schema(gen)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_secret_is_resolved() {
+    env::set_var("RUSTY_SAILOR_TEST_SECRET", "s3cret");
+    let secret = Secret::resolve("env:RUSTY_SAILOR_TEST_SECRET").unwrap();
+    assert_eq!(secret.expose(), "s3cret");
+    env::remove_var("RUSTY_SAILOR_TEST_SECRET");
+    assert!(Secret::resolve("env:RUSTY_SAILOR_TEST_SECRET").is_err());
+
+    let file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(file.path(), "s3cret\n").unwrap();
+    let secret = Secret::resolve(&format!("file:{}", file.path().display())).unwrap();
+    assert_eq!(secret.expose(), "s3cret");
+    assert!(Secret::resolve("file:/nonexistent/secret").is_err());
+
+    assert_eq!(Secret::resolve("s3cret").unwrap().expose(), "s3cret");
+  }
+
+  #[test]
+  fn test_secret_is_never_shown() {
+    env::set_var("RUSTY_SAILOR_TEST_SHOWN_SECRET", "s3cret");
+    let secret = Secret::resolve("env:RUSTY_SAILOR_TEST_SHOWN_SECRET").unwrap();
+    let literal = Secret::resolve("s3cret").unwrap();
+    env::remove_var("RUSTY_SAILOR_TEST_SHOWN_SECRET");
+
+    assert_eq!(format!("{:?}", secret), "Secret(env:RUSTY_SAILOR_TEST_SHOWN_SECRET)");
+    assert_eq!(format!("{:?}", literal), "Secret(<redacted>)");
+    assert_eq!(literal.to_string(), "<redacted>");
+    assert_eq!(serde_json::to_string(&literal).unwrap(), "\"<redacted>\"");
+    assert_ne!(literal.fingerprint("yacht"), Secret::resolve("other").unwrap().fingerprint("yacht"));
+    assert_ne!(literal.fingerprint("yacht"), literal.fingerprint("schooner"));
+    assert!(!literal.fingerprint("yacht").contains("s3cret"));
+    assert_eq!(
+      serde_json::to_string(&secret).unwrap(),
+      "\"env:RUSTY_SAILOR_TEST_SHOWN_SECRET\""
+    );
+  }
+}
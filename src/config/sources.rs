@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Read;
+use std::path::PathBuf;
+
+use config::{Config, ConfigError, File, FileFormat};
+
+use crate::config::{Settings, API_VERSION};
+use crate::config::migration::{is_known_key, API_VERSION_KEY};
+use crate::errors::{ErrorKind, InstallError};
+
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/rusty-sailor/config.toml";
+pub const ENV_PREFIX: &str = "RUSTY_SAILOR";
+pub const STDIN_PATH: &str = "-";
+// Single underscore is already used within key names,
+// e.g. RUSTY_SAILOR_ETCD__LISTEN_CLIENT_PORT
+const ENV_SEPARATOR: &str = "__";
+const DEFAULT_SOURCE_NAME: &str = "default";
+
+#[derive(Clone, Debug)]
+pub enum ConfigFile {
+  // Format is picked by extension: toml, yaml, yml or json
+  Path(String),
+  // Already read contents, e.g. from stdin
+  Contents(String)
+}
+
+// Where configuration is read from, from the lowest priority.
+// Default instance reads nothing, so that only defaults are used
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+  // Skipped, when the file does not exist
+  pub system_path: Option<PathBuf>,
+  pub file: Option<ConfigFile>,
+  pub env_prefix: Option<String>,
+  pub overrides: Vec<(String, String)>
+}
+
+impl ConfigSources {
+  // Path `-` reads the configuration from stdin
+  pub fn standard(
+    path: Option<String>,
+    overrides: Vec<(String, String)>
+  ) -> Result<Self, InstallError> {
+    let file = match path {
+      Some(path) if path == STDIN_PATH => {
+        let mut contents = String::new();
+        std::io::stdin().read_to_string(&mut contents)?;
+        Some(ConfigFile::Contents(contents))
+      },
+      path => path.map(ConfigFile::Path)
+    };
+    Ok(
+      Self {
+        system_path: Some(PathBuf::from(SYSTEM_CONFIG_PATH)),
+        file,
+        env_prefix: Some(ENV_PREFIX.to_string()),
+        overrides
+      }
+    )
+  }
+
+  // Every source is kept in its own layer, so that
+  // it is still known where a value came from.
+  // Anything worth telling the user is added to warnings
+  fn layers(
+    &self,
+    warnings: &mut Vec<String>
+  ) -> Result<Vec<(String, Config)>, ConfigError> {
+    let mut layers = vec![];
+    if let Some(system_path) = &self.system_path {
+      let mut layer = Config::new();
+      layer.merge(File::from(system_path.as_path()).required(false))?;
+      let name = system_path.display().to_string();
+      if system_path.exists() {
+        warnings.extend(_unversioned_warning(&name, &layer));
+      }
+      layers.push((name, layer));
+    }
+    match &self.file {
+      Some(ConfigFile::Path(path)) => {
+        let mut layer = Config::new();
+        layer.merge(File::with_name(path))?;
+        warnings.extend(_unversioned_warning(path, &layer));
+        layers.push((path.clone(), layer));
+      },
+      Some(ConfigFile::Contents(contents)) => {
+        let mut layer = Config::new();
+        layer.merge(File::from_str(contents, _guess_format(contents)))?;
+        warnings.extend(_unversioned_warning("stdin", &layer));
+        layers.push(("stdin".to_string(), layer));
+      },
+      None => {}
+    }
+    if let Some(env_prefix) = &self.env_prefix {
+      let layer = _environment_layer(env_prefix, warnings)?;
+      layers.push((format!("environment ({}_*)", env_prefix), layer));
+    }
+    if !self.overrides.is_empty() {
+      let mut layer = Config::new();
+      for (key, value) in self.overrides.iter() {
+        layer.set(key, value.as_str())?;
+      }
+      layers.push(("--set".to_string(), layer));
+    }
+    Ok(layers)
+  }
+
+  pub fn load(&self) -> Result<Settings, ConfigError> {
+    let mut cfg = Config::new();
+    for (_, layer) in self.layers(&mut vec![])? {
+      cfg.merge(layer)?;
+    }
+    cfg.try_into()
+  }
+
+  // Issues, which do not prevent loading, e.g. unversioned
+  // files or environment variables, that are not settings
+  pub fn warnings(&self) -> Result<Vec<String>, ConfigError> {
+    let mut warnings = vec![];
+    self.layers(&mut warnings)?;
+    Ok(warnings)
+  }
+}
+
+// Files without api_version are read as the current version,
+// even though they may need `config migrate`
+fn _unversioned_warning(
+  name: &str,
+  layer: &Config
+) -> Option<String> {
+  match layer.get_str(API_VERSION_KEY) {
+    Err(ConfigError::NotFound(_)) => Some(
+      format!(
+        "`{}` has no {}, it is read as `{}`, check it with `config migrate`",
+        name,
+        API_VERSION_KEY,
+        API_VERSION
+      )
+    ),
+    _ => None
+  }
+}
+
+// Other tools may use the same prefix, so only variables,
+// which name one of the settings, are taken into account
+fn _environment_layer(
+  env_prefix: &str,
+  warnings: &mut Vec<String>
+) -> Result<Config, ConfigError> {
+  let prefix = format!("{}_", env_prefix);
+  let mut layer = Config::new();
+  for (name, value) in env::vars() {
+    let key = match name.strip_prefix(&prefix) {
+      Some(key) => key.to_lowercase().replace(ENV_SEPARATOR, "."),
+      None => continue
+    };
+    if is_known_key(&key) {
+      layer.set(&key, value)?;
+    }
+    else {
+      warnings.push(format!("Ignoring `{}`, as `{}` is not a known setting", name, key));
+    }
+  }
+  Ok(layer)
+}
+
+// Contents without extension are either TOML or YAML,
+// which in turn covers JSON as well
+fn _guess_format(
+  contents: &str
+) -> FileFormat {
+  match contents.parse::<toml::Value>() {
+    Ok(_) => FileFormat::Toml,
+    Err(_) => FileFormat::Yaml
+  }
+}
+
+// Parses `--set` override given as key=value
+pub fn parse_override(
+  value: &str
+) -> Result<(String, String), InstallError> {
+  let mut parts = value.splitn(2, '=');
+  match (parts.next(), parts.next()) {
+    (Some(key), Some(value)) if !key.trim().is_empty() => Ok(
+      (key.trim().to_string(), value.to_string())
+    ),
+    _ => Err(
+      InstallError::new(
+        ErrorKind::Config,
+        format!("Override `{}` should be given as key=value", value)
+      )
+    )
+  }
+}
+
+// Tables are walked down, everything else
+// (including arrays) is kept as a single value
+fn _flatten(
+  prefix: &str,
+  value: toml::Value,
+  flattened: &mut BTreeMap<String, toml::Value>
+) {
+  match value {
+    toml::Value::Table(table) => {
+      for (key, value) in table {
+        let key = match prefix {
+          "" => key,
+          _ => format!("{}.{}", prefix, key)
+        };
+        _flatten(&key, value, flattened);
+      }
+    },
+    value => {
+      flattened.insert(prefix.to_string(), value);
+    }
+  }
+}
+
+fn _is_set_by(
+  key: &str,
+  layer_keys: &[String]
+) -> bool {
+  layer_keys.iter().any(
+    |x| x == key || key.starts_with(&format!("{}.", x))
+  )
+}
+
+// Every final value, along with name of the source it came from
+pub fn effective_settings(
+  sources: &ConfigSources
+) -> Result<Vec<(String, toml::Value, String)>, InstallError> {
+  let layers = sources.layers(&mut vec![])?
+    .into_iter()
+    .map(|(name, layer)| {
+      let mut flattened = BTreeMap::new();
+      _flatten("", layer.try_into::<toml::Value>()?, &mut flattened);
+      Ok((name, flattened.into_keys().collect()))
+    })
+    .collect::<Result<Vec<(String, Vec<String>)>, ConfigError>>()?;
+
+  let mut flattened = BTreeMap::new();
+  _flatten("", toml::Value::try_from(sources.load()?)?, &mut flattened);
+  Ok(
+    flattened.into_iter().map(|(key, value)| {
+      let source = layers
+        .iter()
+        .rev()
+        .find(|(_, layer_keys)| _is_set_by(&key, layer_keys))
+        .map_or(DEFAULT_SOURCE_NAME, |(name, _)| name.as_str())
+        .to_string();
+      (key, value, source)
+    }).collect()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use std::fs;
+  use super::*;
+
+  fn _write_config(
+    dir: &tempfile::TempDir,
+    name: &str,
+    contents: &str
+  ) -> PathBuf {
+    let path = dir.path().join(name);
+    fs::write(&path, contents).unwrap();
+    path
+  }
+
+  #[test]
+  fn test_layers_are_merged_in_order() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let system_path = _write_config(
+      &dir,
+      "rusty-sailor-system-layer.toml",
+      "debug = true\ninstallation_dir = \"/opt/system\"\n[etcd]\nlisten_client_port = 2400"
+    );
+    let path = _write_config(
+      &dir,
+      "rusty-sailor-file-layer.toml",
+      "installation_dir = \"/opt/file\"\n[etcd]\nlisten_peer_port = 2401"
+    );
+    env::set_var("RUSTY_SAILOR_TEST_LAYERS_ETCD__LISTEN_PEER_PORT", "2402");
+    env::set_var("RUSTY_SAILOR_TEST_LAYERS_FOO", "1");
+    let sources = ConfigSources {
+      system_path: Some(system_path.clone()),
+      file: Some(ConfigFile::Path(path.display().to_string())),
+      env_prefix: Some("RUSTY_SAILOR_TEST_LAYERS".to_string()),
+      overrides: vec![parse_override("pki.ca.common_name=yacht-ca").unwrap()]
+    };
+
+    let settings = sources.load().unwrap();
+    assert!(settings.debug);
+    assert_eq!(settings.installation_dir, "/opt/file");
+    assert_eq!(settings.etcd.listen_client_port, 2400);
+    assert_eq!(settings.etcd.listen_peer_port, 2402);
+    assert_eq!(settings.pki.ca.common_name, "yacht-ca");
+
+    let effective = effective_settings(&sources).unwrap();
+    let source_of = |key: &str| effective.iter()
+      .find(|(x, _, _)| x == key)
+      .map(|(_, _, source)| source.clone())
+      .unwrap();
+    assert_eq!(source_of("debug"), system_path.display().to_string());
+    assert_eq!(source_of("installation_dir"), path.display().to_string());
+    assert_eq!(source_of("etcd.listen_peer_port"), "environment (RUSTY_SAILOR_TEST_LAYERS_*)");
+    assert_eq!(source_of("pki.ca.common_name"), "--set");
+    assert_eq!(source_of("pki.rsa_size"), "default");
+
+    let warnings = sources.warnings().unwrap();
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings.iter().any(|x| x.starts_with(&format!("`{}` has no api_version", path.display()))));
+    assert!(warnings.iter().any(|x| x.contains("`RUSTY_SAILOR_TEST_LAYERS_FOO`")));
+
+    env::remove_var("RUSTY_SAILOR_TEST_LAYERS_ETCD__LISTEN_PEER_PORT");
+    env::remove_var("RUSTY_SAILOR_TEST_LAYERS_FOO");
+  }
+
+  #[test]
+  fn test_yaml_and_json_are_accepted() {
+    let yaml = "etcd:\n  listen_client_port: 2400\n  other_nodes:\n    - name: schooner\n      peer_url: https://10.0.0.2:2380\n";
+    let json = r#"{"etcd": {"listen_client_port": 2400, "initial_cluster_state": "existing"}}"#;
+    let dir = tempfile::TempDir::new().unwrap();
+    for (name, contents) in [
+      ("rusty-sailor-format.yaml", yaml),
+      ("rusty-sailor-format.yml", yaml),
+      ("rusty-sailor-format.json", json)
+    ].iter() {
+      let path = _write_config(&dir, name, contents);
+      let sources = ConfigSources {
+        file: Some(ConfigFile::Path(path.display().to_string())),
+        ..Default::default()
+      };
+      assert_eq!(sources.load().unwrap().etcd.listen_client_port, 2400);
+    }
+
+    for contents in [yaml, json, "[etcd]\nlisten_client_port = 2400"].iter() {
+      let sources = ConfigSources {
+        file: Some(ConfigFile::Contents(contents.to_string())),
+        ..Default::default()
+      };
+      assert_eq!(sources.load().unwrap().etcd.listen_client_port, 2400);
+    }
+  }
+
+  #[test]
+  fn test_parse_override() {
+    assert_eq!(
+      parse_override("etcd.data_dir=/data/etcd=1").unwrap(),
+      ("etcd.data_dir".to_string(), "/data/etcd=1".to_string())
+    );
+    assert!(parse_override("debug").is_err());
+    assert!(parse_override("=true").is_err());
+  }
+}
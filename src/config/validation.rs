@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{
+  ArtifactSource, ClusterState, EtcdSettings, LogFileMode, LoggingSettings, PkiSettings, Settings, API_VERSION
+};
+use crate::errors::{ErrorKind, InstallError};
+
+const MIN_RSA_SIZE: u32 = 2048;
+const MAX_RSA_SIZE: u32 = 16384;
+
+// Collects every issue, so that all of them
+// can be reported at once
+#[derive(Default)]
+struct Issues {
+  issues: Vec<(String, String)>
+}
+
+impl Issues {
+  fn check(
+    &mut self,
+    field: &str,
+    is_valid: bool,
+    msg: &str
+  ) {
+    if !is_valid {
+      self.issues.push((field.to_string(), msg.to_string()));
+    }
+  }
+
+  fn finish(self) -> Result<(), InstallError> {
+    if self.issues.is_empty() {
+      return Ok(())
+    }
+    let details = self.issues
+      .iter()
+      .map(|(field, msg)| format!("  - {}: {}", field, msg))
+      .collect::<Vec<String>>()
+      .join("\n");
+    Err(
+      InstallError::new(
+        ErrorKind::Config,
+        format!("{} configuration error(s) found:\n{}", self.issues.len(), details)
+      )
+    )
+  }
+}
+
+// RFC 1123 hostname, labels separated by dots
+fn _is_valid_hostname(
+  hostname: &str
+) -> bool {
+  !hostname.is_empty()
+    && hostname.len() <= 253
+    && hostname.split('.').all(|label| {
+      !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+fn _is_absolute(
+  path: &str
+) -> bool {
+  Path::new(path).is_absolute()
+}
+
+fn _validate_etcd(
+  etcd: &EtcdSettings,
+  hostname: &str,
+  issues: &mut Issues
+) {
+  issues.check("etcd.data_dir", _is_absolute(&etcd.data_dir), "has to be an absolute path");
+  issues.check("etcd.listen_client_port", etcd.listen_client_port != 0, "can not be 0");
+  issues.check("etcd.listen_peer_port", etcd.listen_peer_port != 0, "can not be 0");
+  issues.check(
+    "etcd.listen_peer_port",
+    etcd.listen_peer_port != etcd.listen_client_port,
+    "has to differ from etcd.listen_client_port"
+  );
+  issues.check(
+    "etcd.peer_connect_timeout",
+    etcd.peer_connect_timeout != Duration::from_secs(0),
+    "can not be 0"
+  );
+
+  let other_nodes = etcd.other_nodes.as_deref().unwrap_or_default();
+  issues.check(
+    "etcd.other_nodes",
+    etcd.initial_cluster_state != ClusterState::Existing || !other_nodes.is_empty(),
+    "at least one node is required, when joining existing cluster"
+  );
+
+  let mut names = HashSet::new();
+  for (i, node) in other_nodes.iter().enumerate() {
+    let field = format!("etcd.other_nodes[{}]", i);
+    issues.check(
+      &format!("{}.name", field),
+      _is_valid_hostname(&node.name),
+      &format!("`{}` is not a valid member name", node.name)
+    );
+    issues.check(
+      &format!("{}.name", field),
+      node.name != hostname,
+      "has to differ from hostname of this node"
+    );
+    issues.check(
+      &format!("{}.name", field),
+      names.insert(node.name.as_str()),
+      &format!("`{}` is defined more than once", node.name)
+    );
+
+    let url = &node.peer_url;
+    issues.check(
+      &format!("{}.peer_url", field),
+      url.scheme() == "https",
+      &format!("`{}` has to use https scheme", url)
+    );
+    issues.check(
+      &format!("{}.peer_url", field),
+      url.host().is_some() && url.port().is_some(),
+      &format!("`{}` has to include both host and port", url)
+    );
+    issues.check(
+      &format!("{}.peer_url", field),
+      url.path() == "/" && url.query().is_none() && url.fragment().is_none(),
+      &format!("`{}` can not have path, query nor fragment", url)
+    );
+  }
+}
+
+fn _validate_logging(
+  logging: &LoggingSettings,
+  issues: &mut Issues
+) {
+  issues.check(
+    "logging.file",
+    logging.file.as_ref().is_none_or(|x| _is_absolute(x)),
+    "has to be an absolute path"
+  );
+  issues.check(
+    "logging.keep_files",
+    logging.file_mode != LogFileMode::Rotate || logging.keep_files > 0,
+    "can not be 0, when rotating"
+  );
+  for module in logging.modules.keys() {
+    issues.check(
+      "logging.modules",
+      !module.is_empty() && module.split("::").all(|x| !x.is_empty()),
+      &format!("`{}` is not a valid module path", module)
+    );
+  }
+}
+
+fn _validate_pki(
+  pki: &PkiSettings,
+  issues: &mut Issues
+) {
+  issues.check(
+    "pki.country_name",
+    pki.country_name.len() == 2 && pki.country_name.chars().all(|c| c.is_ascii_alphabetic()),
+    "has to be two letter country code"
+  );
+  issues.check(
+    "pki.email_address",
+    pki.email_address.contains('@'),
+    &format!("`{}` is not a valid email address", pki.email_address)
+  );
+  issues.check(
+    "pki.rsa_size",
+    (MIN_RSA_SIZE..=MAX_RSA_SIZE).contains(&pki.rsa_size),
+    &format!("has to be between {} and {}", MIN_RSA_SIZE, MAX_RSA_SIZE)
+  );
+  issues.check("pki.ca.common_name", !pki.ca.common_name.is_empty(), "can not be empty");
+  issues.check("pki.ca.expiry_in_days", pki.ca.expiry_in_days > 0, "can not be 0");
+  issues.check(
+    "pki.ca.key_passphrase",
+    pki.ca.key_passphrase.as_ref().is_none_or(|x| !x.expose().is_empty()),
+    "can not be empty"
+  );
+}
+
+pub fn validate(
+  settings: &Settings
+) -> Result<(), InstallError> {
+  let mut issues = Issues::default();
+  issues.check(
+    "api_version",
+    settings.api_version == API_VERSION,
+    &format!(
+      "`{}` is not supported, upgrade the file with `config migrate` to `{}`",
+      settings.api_version,
+      API_VERSION
+    )
+  );
+  issues.check(
+    "hostname",
+    _is_valid_hostname(&settings.hostname),
+    &format!("`{}` is not a valid hostname", settings.hostname)
+  );
+  issues.check(
+    "installation_dir",
+    _is_absolute(&settings.installation_dir),
+    "has to be an absolute path"
+  );
+  match &settings.artifacts {
+    ArtifactSource::Embedded => {},
+    ArtifactSource::Directory { path, manifest_sha256 }
+      | ArtifactSource::Bundle { path, manifest_sha256 } => {
+      issues.check("artifacts.path", _is_absolute(path), "has to be an absolute path");
+      issues.check(
+        "artifacts.manifest_sha256",
+        manifest_sha256.len() == 64 && manifest_sha256.chars().all(|x| x.is_ascii_hexdigit()),
+        "has to be hex encoded sha256 of manifest.toml"
+      );
+    }
+  }
+  _validate_etcd(&settings.etcd, &settings.hostname, &mut issues);
+  _validate_logging(&settings.logging, &mut issues);
+  _validate_pki(&settings.pki, &mut issues);
+  issues.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use url::Url;
+  use crate::config::EtcdNode;
+  use super::*;
+
+  fn _settings() -> Settings {
+    Settings {
+      hostname: "yacht".to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_defaults_are_valid() {
+    assert!(validate(&_settings()).is_ok());
+  }
+
+  #[test]
+  fn test_every_error_is_reported() {
+    let mut settings = _settings();
+    settings.installation_dir = "rusty-sailor".to_string();
+    settings.artifacts = ArtifactSource::Bundle {
+      path: "bundle.tar".to_string(),
+      manifest_sha256: "c38c".to_string()
+    };
+    settings.etcd.initial_cluster_state = ClusterState::Existing;
+    settings.etcd.listen_peer_port = settings.etcd.listen_client_port;
+    settings.pki.rsa_size = 1024;
+
+    let error = validate(&settings).unwrap_err();
+    assert!(error.msg.starts_with("6 configuration error(s) found"));
+    assert!(error.msg.contains("  - installation_dir: has to be an absolute path"));
+    assert!(error.msg.contains("  - artifacts.path: has to be an absolute path"));
+    assert!(error.msg.contains("  - artifacts.manifest_sha256: has to be hex encoded sha256"));
+    assert!(error.msg.contains("  - etcd.other_nodes: at least one node"));
+  }
+
+  #[test]
+  fn test_other_nodes_are_validated() {
+    let node = |name: &str, url: &str| EtcdNode {
+      name: name.to_string(),
+      peer_url: Url::parse(url).unwrap()
+    };
+    let mut settings = _settings();
+    settings.etcd.other_nodes = Some(vec![
+      node("schooner", "https://10.0.0.2:2380"),
+      node("schooner", "http://10.0.0.3:2380"),
+      node("yacht", "https://10.0.0.4"),
+      node("-ketch", "https://10.0.0.5:2380/peers")
+    ]);
+
+    let error = validate(&settings).unwrap_err();
+    assert!(error.msg.starts_with("6 configuration error(s) found"));
+    assert!(error.msg.contains("etcd.other_nodes[1].name: `schooner` is defined more than once"));
+    assert!(error.msg.contains("etcd.other_nodes[1].peer_url"));
+    assert!(error.msg.contains("etcd.other_nodes[2].name: has to differ"));
+    assert!(error.msg.contains("etcd.other_nodes[2].peer_url: `https://10.0.0.4/` has to include"));
+    assert!(error.msg.contains("etcd.other_nodes[3].peer_url"));
+  }
+}
@@ -1,24 +1,76 @@
 use std::convert::From;
+use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+// Every kind has its own process exit code, so that wrapper
+// scripts can tell failures apart. These codes are stable,
+// a new kind gets a new code and the old ones are never reused:
+//
+//    1  Other
+//   10  Config
+//   11  CustomCANotSet
+//   12  BindAddress
+//   20  Preflight
+//   30  FileIo
+//   31  UnpackArchive
+//   32  TemplateRender
+//   33  Serialization
+//   34  Logger
+//   40  OpenSSL
+//   50  Command
+//   51  Systemd
+//   52  Etcd
+//   60  Component
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ErrorKind {
   BindAddress,
+  Command,
+  Component,
   Config,
   CustomCANotSet,
+  Etcd,
   FileIo,
   Logger,
   OpenSSL,
+  Preflight,
+  Serialization,
   Systemd,
   TemplateRender,
   UnpackArchive,
   Other
 }
 
+impl ErrorKind {
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      ErrorKind::Other => 1,
+      ErrorKind::Config => 10,
+      ErrorKind::CustomCANotSet => 11,
+      ErrorKind::BindAddress => 12,
+      ErrorKind::Preflight => 20,
+      ErrorKind::FileIo => 30,
+      ErrorKind::UnpackArchive => 31,
+      ErrorKind::TemplateRender => 32,
+      ErrorKind::Serialization => 33,
+      ErrorKind::Logger => 34,
+      ErrorKind::OpenSSL => 40,
+      ErrorKind::Command => 50,
+      ErrorKind::Systemd => 51,
+      ErrorKind::Etcd => 52,
+      ErrorKind::Component => 60
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct InstallError {
   pub kind: ErrorKind,
-  pub msg: String
+  pub msg: String,
+  // What was being done, from the innermost,
+  // e.g. "while writing etcd peer certificate to <path>"
+  pub context: Vec<String>,
+  source: Option<Arc<dyn Error + Send + Sync>>
 }
 
 impl InstallError {
@@ -28,17 +80,27 @@ impl InstallError {
   ) -> Self {
     Self {
       kind,
-      msg
+      msg,
+      context: vec![],
+      source: None
     }
   }
 
   pub fn new_from_str(
     kind: ErrorKind,
     msg: &str
+  ) -> Self {
+    Self::new(kind, msg.to_string())
+  }
+
+  pub fn with_source<E: Error + Send + Sync + 'static>(
+    kind: ErrorKind,
+    msg: String,
+    source: E
   ) -> Self {
     Self {
-      kind,
-      msg: msg.to_string()
+      source: Some(Arc::new(source)),
+      ..Self::new(kind, msg)
     }
   }
 
@@ -48,52 +110,209 @@ impl InstallError {
       "Custom CA was not provided for the installer run."
     )
   }
+
+  pub fn add_context(
+    mut self,
+    context: String
+  ) -> Self {
+    self.context.push(context);
+    self
+  }
+
+  pub fn exit_code(&self) -> i32 {
+    self.kind.exit_code()
+  }
+
+  // Messages of every underlying error. Converted errors
+  // reuse message of their source, which is not repeated
+  pub fn causes(&self) -> Vec<String> {
+    let mut causes = vec![];
+    let mut cause = self.source();
+    let mut previous = self.msg.clone();
+    while let Some(error) = cause {
+      let message = error.to_string();
+      if message != previous {
+        causes.push(message.clone());
+      }
+      previous = message;
+      cause = error.source();
+    }
+    causes
+  }
+
+  // Message along with every underlying cause, one per line
+  pub fn report(&self) -> String {
+    let mut report = self.to_string();
+    for cause in self.causes() {
+      report.push_str(&format!("\n  caused by: {}", cause));
+    }
+    report
+  }
 }
 
 impl fmt::Display for InstallError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f,"{}",self.msg)
+    write!(f, "{}", self.msg)?;
+    for context in self.context.iter() {
+      write!(f, ", {}", context)?;
+    }
+    Ok(())
+  }
+}
+
+impl Error for InstallError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    self.source.as_ref().map(|x| x.as_ref() as &(dyn Error + 'static))
+  }
+}
+
+// Adds context to any error, that converts into InstallError
+pub trait Context<T> {
+  fn context(
+    self,
+    context: &str
+  ) -> Result<T, InstallError>;
+
+  fn with_context<F: FnOnce() -> String>(
+    self,
+    context: F
+  ) -> Result<T, InstallError>;
+}
+
+impl<T, E: Into<InstallError>> Context<T> for Result<T, E> {
+  fn context(
+    self,
+    context: &str
+  ) -> Result<T, InstallError> {
+    self.map_err(|e| e.into().add_context(context.to_string()))
+  }
+
+  fn with_context<F: FnOnce() -> String>(
+    self,
+    context: F
+  ) -> Result<T, InstallError> {
+    self.map_err(|e| e.into().add_context(context()))
   }
 }
 
 impl From<openssl::error::ErrorStack> for InstallError {
   fn from(error: openssl::error::ErrorStack) -> Self {
-    InstallError::new(ErrorKind::OpenSSL, error.to_string())
+    InstallError::with_source(ErrorKind::OpenSSL, error.to_string(), error)
   }
 }
 
 impl From<std::io::Error> for InstallError {
   fn from(error: std::io::Error) -> Self {
-    InstallError::new(ErrorKind::FileIo, error.to_string())
+    InstallError::with_source(ErrorKind::FileIo, error.to_string(), error)
   }
 }
 
 impl From<config::ConfigError> for InstallError {
   fn from(error: config::ConfigError) -> Self {
-    InstallError::new(ErrorKind::Config, error.to_string())
+    InstallError::with_source(ErrorKind::Config, error.to_string(), error)
   }
 }
 
 impl From<log::SetLoggerError> for InstallError {
   fn from(error: log::SetLoggerError) -> Self {
-    InstallError::new(ErrorKind::Logger, error.to_string())
+    InstallError::with_source(ErrorKind::Logger, error.to_string(), error)
   }
 }
 
 impl From<askama::shared::Error> for InstallError {
   fn from(error: askama::shared::Error) -> Self {
-    InstallError::new(ErrorKind::TemplateRender, error.to_string())
+    InstallError::with_source(ErrorKind::TemplateRender, error.to_string(), error)
   }
 }
 
 impl From<std::string::FromUtf8Error> for InstallError {
   fn from(error: std::string::FromUtf8Error) -> Self {
-    InstallError::new(ErrorKind::Config, error.to_string())
+    InstallError::with_source(ErrorKind::Config, error.to_string(), error)
   }
 }
 
 impl From<std::net::AddrParseError> for InstallError {
   fn from(error: std::net::AddrParseError) -> Self {
-    InstallError::new(ErrorKind::Config, error.to_string())
+    InstallError::with_source(ErrorKind::Config, error.to_string(), error)
+  }
+}
+
+impl From<serde_json::Error> for InstallError {
+  fn from(error: serde_json::Error) -> Self {
+    InstallError::with_source(ErrorKind::Serialization, error.to_string(), error)
   }
-}
\ No newline at end of file
+}
+
+impl From<toml::ser::Error> for InstallError {
+  fn from(error: toml::ser::Error) -> Self {
+    InstallError::with_source(ErrorKind::Config, error.to_string(), error)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::io;
+  use super::*;
+
+  #[test]
+  fn test_source_and_context_are_kept() {
+    let result: Result<(), io::Error> = Err(
+      io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied")
+    );
+    let error = result
+      .with_context(|| "while writing etcd peer certificate to /opt/etcd-peer.pem".to_string())
+      .context("while installing `etcd`")
+      .err()
+      .unwrap();
+
+    assert_eq!(error.kind, ErrorKind::FileIo);
+    assert_eq!(
+      error.to_string(),
+      "Permission denied, while writing etcd peer certificate \
+      to /opt/etcd-peer.pem, while installing `etcd`"
+    );
+    assert_eq!(error.source().unwrap().to_string(), "Permission denied");
+    assert_eq!(error.report(), error.to_string());
+  }
+
+  #[test]
+  fn test_report_lists_causes() {
+    let source = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+    let error = InstallError::with_source(
+      ErrorKind::Config,
+      "Secret file could not be read".to_string(),
+      source
+    );
+    assert_eq!(
+      error.report(),
+      "Secret file could not be read\n  caused by: No such file or directory"
+    );
+  }
+
+  #[test]
+  fn test_exit_codes_are_unique() {
+    let kinds = [
+      ErrorKind::BindAddress,
+      ErrorKind::Command,
+      ErrorKind::Component,
+      ErrorKind::Config,
+      ErrorKind::CustomCANotSet,
+      ErrorKind::Etcd,
+      ErrorKind::FileIo,
+      ErrorKind::Logger,
+      ErrorKind::OpenSSL,
+      ErrorKind::Preflight,
+      ErrorKind::Serialization,
+      ErrorKind::Systemd,
+      ErrorKind::TemplateRender,
+      ErrorKind::UnpackArchive,
+      ErrorKind::Other
+    ];
+    let codes = kinds.iter().map(|x| x.exit_code()).collect::<HashSet<i32>>();
+    assert_eq!(codes.len(), kinds.len());
+    assert!(!codes.contains(&0));
+    assert_eq!(ErrorKind::Preflight.exit_code(), 20);
+    assert_eq!(ErrorKind::Systemd.exit_code(), 51);
+  }
+}
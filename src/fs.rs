@@ -1,8 +1,56 @@
 use std::collections::HashSet;
 use std::ffi::OsString;
-use std::fs::{copy, read_dir, read_link, remove_dir, remove_file};
-use std::io;
-use std::path::Path;
+use std::fs::{
+  copy, read_dir, read_link, remove_dir, remove_file, rename, set_permissions,
+  File, OpenOptions, Permissions
+};
+use std::io::{self, Read};
+use std::os::unix::fs::{chown, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Owner {
+  pub uid: u32,
+  pub gid: u32
+}
+
+// Mode and owner of written file - when owner is
+// not given, it is left to whoever runs the installer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileAttributes {
+  pub mode: u32,
+  pub owner: Option<Owner>
+}
+
+impl FileAttributes {
+  // Private keys
+  pub const PRIVATE: FileAttributes = FileAttributes { mode: 0o600, owner: None };
+  // Configs, units and certificates
+  pub const PUBLIC: FileAttributes = FileAttributes { mode: 0o644, owner: None };
+}
+
+pub fn backup_path(
+  path: &Path
+) -> PathBuf {
+  let mut backup = path.as_os_str().to_os_string();
+  backup.push(".bak");
+  PathBuf::from(backup)
+}
+
+// Re-roots an absolute path under given directory, so
+// that `/etc/sysctl.d` becomes `<root>/etc/sysctl.d`
+pub fn rooted(
+  root: Option<&Path>,
+  path: &Path
+) -> PathBuf {
+  match root {
+    Some(root) => root.join(
+      path.strip_prefix("/").unwrap_or(path)
+    ),
+    None => path.to_path_buf()
+  }
+}
 
 // Rename will not work across different mount-points
 pub fn mv(
@@ -17,13 +65,86 @@ pub fn mv(
   remove_file(file_path)
 }
 
+// Contents are written to a temporary file next to the destination,
+// which is then renamed over it - so that interrupted write never
+// leaves half of the file behind. Replaced file is kept as <path>.bak
+pub fn write_atomic(
+  path: &Path,
+  bytes: &[u8],
+  mode: u32,
+  owner: Option<Owner>
+) -> io::Result<()> {
+  _replace(path, &mut &bytes[..], mode, owner, true)
+}
+
+// Same as write_atomic, but streamed and without backup,
+// e.g. for unpacked files, whose backups are kept by the caller
+pub fn replace_atomic(
+  path: &Path,
+  reader: &mut dyn Read,
+  mode: u32,
+  owner: Option<Owner>
+) -> io::Result<()> {
+  _replace(path, reader, mode, owner, false)
+}
+
+fn _replace(
+  path: &Path,
+  reader: &mut dyn Read,
+  mode: u32,
+  owner: Option<Owner>,
+  keep_backup: bool
+) -> io::Result<()> {
+  let parent = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent,
+    _ => Path::new(".")
+  };
+  let mut temporary_name = OsString::from(".");
+  temporary_name.push(file_name(path)?);
+  temporary_name.push(format!(".{}.tmp", process::id()));
+  let temporary_path = parent.join(temporary_name);
+
+  let result = _write_new(&temporary_path, reader, mode, owner).and_then(|_| {
+    if keep_backup && path.is_file() {
+      copy(path, backup_path(path))?;
+    }
+    rename(&temporary_path, path)
+  });
+  if result.is_err() {
+    let _ = remove_file(&temporary_path);
+  }
+  result?;
+  // Makes the rename itself durable
+  File::open(parent)?.sync_all()
+}
+
+fn _write_new(
+  path: &Path,
+  reader: &mut dyn Read,
+  mode: u32,
+  owner: Option<Owner>
+) -> io::Result<()> {
+  let mut file = OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .mode(mode)
+    .open(path)?;
+  io::copy(reader, &mut file)?;
+  // Mode given on creation is narrowed by umask
+  set_permissions(path, Permissions::from_mode(mode))?;
+  if let Some(owner) = owner {
+    chown(path, Some(owner.uid), Some(owner.gid))?;
+  }
+  file.sync_all()
+}
+
 pub fn flatten(
   path: &Path,
   file_name_whitelist: Option<&HashSet<OsString>>
 ) -> io::Result<()> {
   let cannonical_path = path.canonicalize()?;
   let cannonical_path = cannonical_path.as_path();
-  _flatten(&cannonical_path, &cannonical_path, file_name_whitelist)
+  _flatten(cannonical_path, cannonical_path, file_name_whitelist)
 }
 
 fn file_name(
@@ -98,3 +219,42 @@ fn _flatten(
   }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_write_atomic() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("etcd-peer.private-key.pem");
+
+    write_atomic(&path, b"first", 0o600, None).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert!(!backup_path(&path).exists());
+
+    write_atomic(&path, b"second", 0o644, None).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o644);
+    assert_eq!(std::fs::read_to_string(backup_path(&path)).unwrap(), "first");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+  }
+
+  #[test]
+  fn test_rooted() {
+    let root = Path::new("/mnt/image");
+    assert_eq!(
+      rooted(Some(root), Path::new("/etc/systemd/system/etcd.service")),
+      PathBuf::from("/mnt/image/etc/systemd/system/etcd.service")
+    );
+    assert_eq!(
+      rooted(Some(root), Path::new("relative/dir")),
+      PathBuf::from("/mnt/image/relative/dir")
+    );
+    assert_eq!(
+      rooted(None, Path::new("/etc/sysctl.d")),
+      PathBuf::from("/etc/sysctl.d")
+    );
+  }
+}
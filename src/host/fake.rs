@@ -0,0 +1,308 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::rc::Rc;
+
+use crate::errors::{ErrorKind, InstallError};
+use crate::fs::{backup_path, FileAttributes};
+use crate::host::Host;
+use crate::vendored::ArtifactRequest;
+
+#[derive(Default)]
+pub struct FakeHostState {
+  pub directories: BTreeSet<PathBuf>,
+  pub files: BTreeMap<PathBuf, Vec<u8>>,
+  pub attributes: BTreeMap<PathBuf, FileAttributes>,
+  pub unpacked_archives: Vec<(String, PathBuf)>,
+  pub commands: Vec<Vec<String>>,
+  pub failing_commands: HashSet<String>,
+  pub command_outputs: HashMap<String, String>,
+  pub enabled_units: BTreeSet<String>,
+  pub users: HashMap<String, u32>,
+  pub groups: HashMap<String, u32>
+}
+
+// In-memory host, which allows to execute whole
+// components without root privileges. Clones share
+// the state, so that test may inspect it after host
+// has been handed over to InstallCtx
+#[derive(Clone, Default)]
+pub struct FakeHost {
+  state: Rc<RefCell<FakeHostState>>
+}
+
+impl FakeHost {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn state(&self) -> Ref<'_, FakeHostState> {
+    self.state.borrow()
+  }
+
+  pub fn state_mut(&self) -> RefMut<'_, FakeHostState> {
+    self.state.borrow_mut()
+  }
+
+  pub fn file_contents(
+    &self,
+    path: &Path
+  ) -> Option<String> {
+    self.state().files.get(path).map(
+      |x| String::from_utf8_lossy(x).to_string()
+    )
+  }
+
+  // Every command, which program name or full
+  // command line matches will exit with status 1
+  pub fn fail_command(
+    &self,
+    command: &str
+  ) {
+    self.state_mut().failing_commands.insert(command.to_string());
+  }
+
+  // Matched the same way as failing commands
+  pub fn set_command_output(
+    &self,
+    command: &str,
+    stdout: &str
+  ) {
+    self.state_mut().command_outputs.insert(command.to_string(), stdout.to_string());
+  }
+}
+
+fn _exit_status(
+  code: i32
+) -> ExitStatus {
+  // Raw wait status keeps exit code in the second byte
+  ExitStatus::from_raw(code << 8)
+}
+
+impl Host for FakeHost {
+  fn create_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    for ancestor in path.ancestors() {
+      state.directories.insert(ancestor.to_path_buf());
+    }
+    Ok(())
+  }
+
+  fn write_file(
+    &self,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    let has_parent = path.parent().is_none_or(
+      |parent| state.directories.contains(parent)
+    );
+    if !has_parent {
+      return Err(
+        InstallError::new(
+          ErrorKind::FileIo,
+          format!("Parent directory of `{}` does not exist", path.display())
+        )
+      )
+    }
+    let previous_contents = state.files.insert(path.to_path_buf(), contents.to_vec());
+    if let Some(previous_contents) = previous_contents {
+      state.files.insert(backup_path(path), previous_contents);
+    }
+    state.attributes.insert(path.to_path_buf(), attributes);
+    Ok(())
+  }
+
+  fn read_file(
+    &self,
+    path: &Path
+  ) -> Result<Option<Vec<u8>>, InstallError> {
+    Ok(self.state().files.get(path).cloned())
+  }
+
+  fn path_exists(
+    &self,
+    path: &Path
+  ) -> bool {
+    let state = self.state();
+    state.directories.contains(path) || state.files.contains_key(path)
+  }
+
+  fn copy_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    let contents = state.files.get(from).cloned().ok_or_else(||
+      InstallError::new(
+        ErrorKind::FileIo,
+        format!("File `{}` does not exist", from.display())
+      )
+    )?;
+    if let Some(attributes) = state.attributes.get(from).copied() {
+      state.attributes.insert(to.to_path_buf(), attributes);
+    }
+    state.files.insert(to.to_path_buf(), contents);
+    Ok(())
+  }
+
+  fn rename_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    self.copy_file(from, to)?;
+    self.remove_file(from)
+  }
+
+  fn remove_file(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    state.attributes.remove(path);
+    match state.files.remove(path) {
+      Some(_) => Ok(()),
+      None => Err(
+        InstallError::new(
+          ErrorKind::FileIo,
+          format!("File `{}` does not exist", path.display())
+        )
+      )
+    }
+  }
+
+  fn remove_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    if !state.directories.contains(path) {
+      return Err(
+        InstallError::new(
+          ErrorKind::FileIo,
+          format!("Directory `{}` does not exist", path.display())
+        )
+      )
+    }
+    state.directories.retain(|x| !x.starts_with(path));
+    state.files.retain(|x, _| !x.starts_with(path));
+    Ok(())
+  }
+
+  fn unpack_archive(
+    &self,
+    artifact: &ArtifactRequest,
+    destination: &Path,
+    file_name_whitelist: Option<&HashSet<OsString>>
+  ) -> Result<(), InstallError> {
+    let mut state = self.state_mut();
+    state.unpacked_archives.push(
+      (artifact.name.to_string(), destination.to_path_buf())
+    );
+    for file_name in file_name_whitelist.into_iter().flatten() {
+      state.files.insert(destination.join(file_name), vec![]);
+    }
+    Ok(())
+  }
+
+  fn run_command(
+    &self,
+    program: &str,
+    args: &[&str]
+  ) -> Result<Output, InstallError> {
+    let mut command = vec![program.to_string()];
+    command.extend(args.iter().map(|x| x.to_string()));
+    let command_line = command.join(" ");
+
+    let mut state = self.state_mut();
+    let should_fail = state.failing_commands.contains(program)
+      || state.failing_commands.contains(&command_line);
+    let stdout = state.command_outputs.get(&command_line)
+      .or_else(|| state.command_outputs.get(program))
+      .map_or_else(Vec::new, |x| x.as_bytes().to_vec());
+    state.commands.push(command);
+    Ok(
+      Output {
+        status: _exit_status(if should_fail { 1 } else { 0 }),
+        stdout,
+        stderr: vec![]
+      }
+    )
+  }
+
+  fn enable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    self.systemctl(&["enable", unit])?;
+    self.state_mut().enabled_units.insert(unit.to_string());
+    Ok(())
+  }
+
+  fn disable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    self.systemctl(&["disable", unit])?;
+    self.state_mut().enabled_units.remove(unit);
+    Ok(())
+  }
+
+  fn is_unit_enabled(
+    &self,
+    unit: &str
+  ) -> bool {
+    self.state().enabled_units.contains(unit)
+  }
+
+  fn lookup_user(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    Ok(self.state().users.get(name).copied())
+  }
+
+  fn lookup_group(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    Ok(self.state().groups.get(name).copied())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_failing_command() {
+    let host = FakeHost::new();
+    host.fail_command("systemctl status etcd.service");
+
+    assert!(host.reload_systemd().is_ok());
+    assert!(host.enable_unit("etcd.service").is_ok());
+    assert!(host.check_unit_status("etcd.service").is_err());
+    assert_eq!(host.state().commands.len(), 3);
+    assert!(host.state().enabled_units.contains("etcd.service"));
+  }
+
+  #[test]
+  fn test_write_requires_parent_directory() {
+    let host = FakeHost::new();
+    let path = Path::new("/etc/systemd/system/etcd.service");
+    assert!(host.write_file(path, b"[Unit]", FileAttributes::PUBLIC).is_err());
+
+    host.create_dir_all(Path::new("/etc/systemd/system")).unwrap();
+    assert!(host.write_file(path, b"[Unit]", FileAttributes::PUBLIC).is_ok());
+    assert_eq!(host.file_contents(path), Some("[Unit]".to_string()));
+  }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::{read, read_to_string};
+use std::io;
+use std::path::Path;
+use std::process::Output;
+
+use crate::errors::{ErrorKind, InstallError};
+use crate::fs::{rooted, FileAttributes};
+use crate::process;
+use crate::vendored::ArtifactRequest;
+
+pub mod fake;
+pub mod recording;
+pub mod system;
+pub mod tracking;
+
+const PASSWD_DB_PATH: &str = "/etc/passwd";
+const GROUP_DB_PATH: &str = "/etc/group";
+
+// Every side effect, that installation has on the node,
+// has to go through this trait - so that the very same
+// components can be executed for real, only planned or
+// against in-memory fake in tests
+pub trait Host {
+  // Directory, under which every path is placed
+  // when installing into a mounted image
+  fn root(&self) -> Option<&Path> {
+    None
+  }
+
+  fn create_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError>;
+
+  // Replaces file atomically, keeping previous one as <path>.bak
+  fn write_file(
+    &self,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError>;
+
+  // Reading has no side effects, so by default
+  // it is done straight from the (rooted) filesystem
+  fn read_file(
+    &self,
+    path: &Path
+  ) -> Result<Option<Vec<u8>>, InstallError> {
+    match read(rooted(self.root(), path)) {
+      Ok(contents) => Ok(Some(contents)),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e.into())
+    }
+  }
+
+  fn path_exists(
+    &self,
+    path: &Path
+  ) -> bool {
+    rooted(self.root(), path).exists()
+  }
+
+  // Keeps mode of copied file
+  fn copy_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError>;
+
+  // Replaces `to`, if it exists
+  fn rename_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError>;
+
+  fn remove_file(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError>;
+
+  fn remove_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError>;
+
+  fn unpack_archive(
+    &self,
+    artifact: &ArtifactRequest,
+    destination: &Path,
+    file_name_whitelist: Option<&HashSet<OsString>>
+  ) -> Result<(), InstallError>;
+
+  fn run_command(
+    &self,
+    program: &str,
+    args: &[&str]
+  ) -> Result<Output, InstallError>;
+
+  // Unsuccessful exit becomes an error of given kind,
+  // carrying the tail of command's stderr
+  fn run_checked(
+    &self,
+    program: &str,
+    args: &[&str],
+    kind: ErrorKind
+  ) -> Result<Output, InstallError> {
+    let output = self.run_command(program, args)?;
+    process::check(program, args, output, kind)
+  }
+
+  fn systemctl(
+    &self,
+    args: &[&str]
+  ) -> Result<(), InstallError> {
+    self.run_checked("systemctl", args, ErrorKind::Systemd)?;
+    Ok(())
+  }
+
+  // There is no running systemd inside of an image,
+  // so only enabling of units is possible there
+  fn reload_systemd(
+    &self
+  ) -> Result<(), InstallError> {
+    match self.root() {
+      Some(_) => Ok(()),
+      None => self.systemctl(&["daemon-reload"])
+    }
+  }
+
+  fn enable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    match self.root() {
+      Some(root) => self.systemctl(
+        &[&format!("--root={}", root.display()), "enable", unit]
+      ),
+      None => self.systemctl(&["enable", unit])
+    }
+  }
+
+  fn stop_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    match self.root() {
+      Some(_) => Ok(()),
+      None => self.systemctl(&["stop", unit])
+    }
+  }
+
+  fn disable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    match self.root() {
+      Some(root) => self.systemctl(
+        &[&format!("--root={}", root.display()), "disable", unit]
+      ),
+      None => self.systemctl(&["disable", unit])
+    }
+  }
+
+  fn is_unit_enabled(
+    &self,
+    unit: &str
+  ) -> bool {
+    let output = match self.root() {
+      Some(root) => self.run_command(
+        "systemctl",
+        &[&format!("--root={}", root.display()), "is-enabled", unit]
+      ),
+      None => self.run_command("systemctl", &["is-enabled", unit])
+    };
+    output.is_ok_and(|x| x.status.success())
+  }
+
+  fn check_unit_status(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    match self.root() {
+      Some(_) => Ok(()),
+      None => self.systemctl(&["status", unit])
+    }
+  }
+
+  fn lookup_user(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    lookup_id(&rooted(self.root(), Path::new(PASSWD_DB_PATH)), name)
+  }
+
+  fn lookup_group(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    lookup_id(&rooted(self.root(), Path::new(GROUP_DB_PATH)), name)
+  }
+}
+
+// Both /etc/passwd and /etc/group share the
+// `name:password:id:...` layout
+pub fn lookup_id(
+  database_path: &Path,
+  name: &str
+) -> Result<Option<u32>, InstallError> {
+  let database = read_to_string(database_path)?;
+  let id = database
+    .lines()
+    .map(|line| line.split(':').collect::<Vec<&str>>())
+    .find(|fields| fields.len() > 2 && fields[0] == name)
+    .and_then(|fields| fields[2].parse::<u32>().ok());
+  Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+  use tempfile::TempDir;
+  use super::*;
+
+  #[test]
+  fn test_lookup_id() {
+    let root = TempDir::new().unwrap();
+    let passwd_path = root.path().join("passwd");
+    std::fs::write(
+      &passwd_path,
+      "root:x:0:0:root:/root:/bin/bash\n\
+      etcd:x:998:996:etcd user:/var/lib/etcd:/sbin/nologin\n"
+    ).unwrap();
+
+    assert_eq!(lookup_id(&passwd_path, "root").unwrap(), Some(0));
+    assert_eq!(lookup_id(&passwd_path, "etcd").unwrap(), Some(998));
+    assert_eq!(lookup_id(&passwd_path, "kubelet").unwrap(), None);
+  }
+}
@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::read;
+use std::net::IpAddr;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+
+use openssl::x509::{X509, X509NameRef};
+use similar::TextDiff;
+
+use crate::errors::InstallError;
+use crate::fs::{backup_path, rooted, FileAttributes};
+use crate::host::Host;
+use crate::vendored::ArtifactRequest;
+
+const PEM_CERTIFICATE_HEADER: &str = "-----BEGIN CERTIFICATE-----";
+// Ends the header of every private key, either plain,
+// encrypted or one of the RSA or EC specific ones
+const PEM_PRIVATE_KEY_MARKER: &str = "PRIVATE KEY-----";
+
+// Does not touch the node - every change is only
+// described (and printed), so that operator may review
+// the installation plan beforehand
+pub struct RecordingHost {
+  planned_actions: RefCell<Vec<String>>,
+  root: Option<PathBuf>
+}
+
+impl RecordingHost {
+  pub fn new() -> Self {
+    Self {
+      planned_actions: RefCell::new(vec![]),
+      root: None
+    }
+  }
+
+  pub fn with_root(
+    root: &Path
+  ) -> Self {
+    Self {
+      planned_actions: RefCell::new(vec![]),
+      root: Some(root.to_path_buf())
+    }
+  }
+
+  pub fn planned_actions(&self) -> Vec<String> {
+    self.planned_actions.borrow().clone()
+  }
+
+  fn _plan(
+    &self,
+    action: String
+  ) {
+    println!("[plan] {}", action);
+    self.planned_actions.borrow_mut().push(action);
+  }
+}
+
+impl Default for RecordingHost {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn _describe_name(
+  name: &X509NameRef
+) -> String {
+  name.entries().map(|entry| {
+    let key = entry.object().nid().short_name().unwrap_or("?");
+    let value = String::from_utf8_lossy(entry.data().as_slice());
+    format!("{}={}", key, value)
+  }).collect::<Vec<String>>().join(", ")
+}
+
+fn _describe_ip(
+  octets: &[u8]
+) -> Option<IpAddr> {
+  match octets.len() {
+    4 => {
+      let mut ip = [0u8; 4];
+      ip.copy_from_slice(octets);
+      Some(IpAddr::from(ip))
+    },
+    16 => {
+      let mut ip = [0u8; 16];
+      ip.copy_from_slice(octets);
+      Some(IpAddr::from(ip))
+    },
+    _ => None
+  }
+}
+
+fn _describe_certificate(
+  certificate: &X509
+) -> String {
+  let alt_names = certificate.subject_alt_names().map_or_else(
+    Vec::new,
+    |names| names.iter().filter_map(|name| {
+      name.dnsname().map(|dns| format!("DNS:{}", dns)).or_else(||
+        name.ipaddress().and_then(_describe_ip).map(|ip| format!("IP:{}", ip))
+      )
+    }).collect::<Vec<String>>()
+  );
+  format!(
+    "certificate\n    subject: {}\n    issuer: {}\n    SANs: [{}]\n    not after: {}",
+    _describe_name(certificate.subject_name()),
+    _describe_name(certificate.issuer_name()),
+    alt_names.join(", "),
+    certificate.not_after()
+  )
+}
+
+fn _describe_contents(
+  path: &Path,
+  contents: &[u8]
+) -> String {
+  let marker = PEM_PRIVATE_KEY_MARKER.as_bytes();
+  if contents.windows(marker.len()).any(|x| x == marker) {
+    return "private key (contents hidden)".to_string()
+  }
+  if contents.starts_with(PEM_CERTIFICATE_HEADER.as_bytes()) {
+    if let Ok(certificate) = X509::from_pem(contents) {
+      return _describe_certificate(&certificate)
+    }
+  }
+
+  let new_contents = match std::str::from_utf8(contents) {
+    Ok(x) => x,
+    Err(_) => return format!("binary file ({} bytes)", contents.len())
+  };
+  let old_contents = read(path).ok()
+    .and_then(|x| String::from_utf8(x).ok())
+    .unwrap_or_default();
+  if old_contents == new_contents {
+    return "unchanged".to_string()
+  }
+  let display_path = path.display().to_string();
+  TextDiff::from_lines(old_contents.as_str(), new_contents)
+    .unified_diff()
+    .header(&display_path, &display_path)
+    .to_string()
+}
+
+impl Host for RecordingHost {
+  fn root(&self) -> Option<&Path> {
+    self.root.as_deref()
+  }
+
+  fn create_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let path = &rooted(self.root(), path);
+    if !path.is_dir() {
+      self._plan(format!("create directory {}", path.display()));
+    }
+    Ok(())
+  }
+
+  fn write_file(
+    &self,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError> {
+    let path = &rooted(self.root(), path);
+    let (verb, backup) = match path.exists() {
+      true => ("overwrite", format!(", keeping {}", backup_path(path).display())),
+      false => ("create", String::new())
+    };
+    let owner = attributes.owner.map_or_else(
+      String::new,
+      |owner| format!(" owned by {}:{}", owner.uid, owner.gid)
+    );
+    self._plan(
+      format!(
+        "{} file {} with mode {:o}{}{}: {}",
+        verb,
+        path.display(),
+        attributes.mode,
+        owner,
+        backup,
+        _describe_contents(path, contents)
+      )
+    );
+    Ok(())
+  }
+
+  fn copy_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    self._plan(
+      format!(
+        "copy file {} to {}",
+        rooted(self.root(), from).display(),
+        rooted(self.root(), to).display()
+      )
+    );
+    Ok(())
+  }
+
+  fn rename_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    self._plan(
+      format!(
+        "move file {} to {}",
+        rooted(self.root(), from).display(),
+        rooted(self.root(), to).display()
+      )
+    );
+    Ok(())
+  }
+
+  fn remove_file(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    self._plan(format!("remove file {}", rooted(self.root(), path).display()));
+    Ok(())
+  }
+
+  fn remove_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    self._plan(format!("remove directory {}", rooted(self.root(), path).display()));
+    Ok(())
+  }
+
+  fn unpack_archive(
+    &self,
+    artifact: &ArtifactRequest,
+    destination: &Path,
+    file_name_whitelist: Option<&HashSet<OsString>>
+  ) -> Result<(), InstallError> {
+    let mut kept_files = file_name_whitelist.map_or_else(
+      || vec!["*".to_string()],
+      |names| names.iter().map(|x| x.to_string_lossy().to_string()).collect()
+    );
+    kept_files.sort();
+    self._plan(
+      format!(
+        "unpack archive {}{} into {} (keeping: {})",
+        artifact.name,
+        artifact.version.map_or_else(String::new, |x| format!(" {}", x)),
+        rooted(self.root(), destination).display(),
+        kept_files.join(", ")
+      )
+    );
+    Ok(())
+  }
+
+  fn run_command(
+    &self,
+    program: &str,
+    args: &[&str]
+  ) -> Result<Output, InstallError> {
+    self._plan(format!("run `{} {}`", program, args.join(" ")));
+    Ok(
+      Output {
+        status: ExitStatus::from_raw(0),
+        stdout: vec![],
+        stderr: vec![]
+      }
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use openssl::pkey::PKey;
+  use openssl::rsa::Rsa;
+  use tempfile::TempDir;
+  use crate::config::secret::Secret;
+  use crate::pki::io::{save_as_encrypted_pem_private_key, save_as_pem_private_key};
+  use super::*;
+
+  #[test]
+  fn test_nothing_is_written() {
+    let root = TempDir::new().unwrap();
+    let host = RecordingHost::new();
+    let dir = root.path().join("etcd");
+    let file = root.path().join("etcd.conf.yml");
+
+    host.create_dir_all(&dir).unwrap();
+    host.write_file(&file, b"name: yacht\n", FileAttributes::PUBLIC).unwrap();
+    host.run_command("systemctl", &["daemon-reload"]).unwrap();
+
+    assert!(!dir.exists());
+    assert!(!file.exists());
+    let actions = host.planned_actions();
+    assert_eq!(actions.len(), 3);
+    assert!(actions[1].contains("+name: yacht"));
+    assert_eq!(actions[2], "run `systemctl daemon-reload`");
+  }
+
+  #[test]
+  fn test_diff_against_existing_file() {
+    let root = TempDir::new().unwrap();
+    let host = RecordingHost::new();
+    let file = root.path().join("etcd.conf.yml");
+    std::fs::write(&file, "name: yacht\ndebug: false\n").unwrap();
+
+    host.write_file(&file, b"name: yacht\ndebug: true\n", FileAttributes::PUBLIC).unwrap();
+    host.write_file(&file, b"name: yacht\ndebug: false\n", FileAttributes::PUBLIC).unwrap();
+
+    let actions = host.planned_actions();
+    assert!(actions[0].starts_with("overwrite file"));
+    assert!(actions[0].contains("with mode 644, keeping"));
+    assert!(actions[0].contains("-debug: false"));
+    assert!(actions[0].contains("+debug: true"));
+    assert!(actions[1].ends_with("unchanged"));
+  }
+
+  #[test]
+  fn test_private_keys_are_hidden() {
+    let root = TempDir::new().unwrap();
+    let host = RecordingHost::new();
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let passphrase = Secret::resolve("s3cret").unwrap();
+
+    save_as_pem_private_key(&host, &key, &root.path().join("plain.pem"), None).unwrap();
+    save_as_encrypted_pem_private_key(
+      &host,
+      &key,
+      &passphrase,
+      &root.path().join("encrypted.pem"),
+      None
+    ).unwrap();
+    host.write_file(
+      &root.path().join("rsa.pem"),
+      &key.rsa().unwrap().private_key_to_pem().unwrap(),
+      FileAttributes::PRIVATE
+    ).unwrap();
+
+    let actions = host.planned_actions();
+    assert_eq!(actions.len(), 3);
+    for action in actions {
+      assert!(action.ends_with("private key (contents hidden)"));
+      assert!(!action.contains("-----BEGIN"));
+    }
+  }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::{copy, create_dir_all, remove_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use crate::errors::InstallError;
+use crate::fs::{flatten, rooted, write_atomic, FileAttributes};
+use crate::host::Host;
+use crate::process;
+use crate::vendored::{self, ArtifactRequest};
+
+// Applies every change directly to the node,
+// or to the image mounted under `root`
+pub struct SystemHost {
+  root: Option<PathBuf>
+}
+
+impl SystemHost {
+  pub fn new() -> Self {
+    Self {
+      root: None
+    }
+  }
+
+  pub fn with_root(
+    root: &Path
+  ) -> Self {
+    Self {
+      root: Some(root.to_path_buf())
+    }
+  }
+
+  fn _rooted(
+    &self,
+    path: &Path
+  ) -> PathBuf {
+    rooted(self.root(), path)
+  }
+}
+
+impl Default for SystemHost {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Host for SystemHost {
+  fn root(&self) -> Option<&Path> {
+    self.root.as_deref()
+  }
+
+  fn create_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    Ok(create_dir_all(self._rooted(path))?)
+  }
+
+  fn write_file(
+    &self,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError> {
+    Ok(write_atomic(&self._rooted(path), contents, attributes.mode, attributes.owner)?)
+  }
+
+  fn copy_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    copy(self._rooted(from), self._rooted(to))?;
+    Ok(())
+  }
+
+  fn rename_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    Ok(rename(self._rooted(from), self._rooted(to))?)
+  }
+
+  fn remove_file(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    Ok(remove_file(self._rooted(path))?)
+  }
+
+  fn remove_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    Ok(remove_dir_all(self._rooted(path))?)
+  }
+
+  fn unpack_archive(
+    &self,
+    artifact: &ArtifactRequest,
+    destination: &Path,
+    file_name_whitelist: Option<&HashSet<OsString>>
+  ) -> Result<(), InstallError> {
+    let destination = self._rooted(destination);
+    match file_name_whitelist {
+      Some(file_names) => vendored::extract_files(artifact, &destination, file_names),
+      None => {
+        vendored::unpack_archive(artifact, &destination)?;
+        Ok(flatten(&destination, None)?)
+      }
+    }
+  }
+
+  fn run_command(
+    &self,
+    program: &str,
+    args: &[&str]
+  ) -> Result<Output, InstallError> {
+    process::run(program, args, process::DEFAULT_TIMEOUT)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tempfile::TempDir;
+  use super::*;
+
+  #[test]
+  fn test_changes_are_applied_under_root() {
+    let root = TempDir::new().unwrap();
+    let host = SystemHost::with_root(root.path());
+
+    host.create_dir_all(Path::new("/etc/sysctl.d")).unwrap();
+    host.write_file(
+      Path::new("/etc/sysctl.d/99-rusty-sailor.conf"),
+      b"net.ipv4.ip_forward = 1\n",
+      FileAttributes::PUBLIC
+    ).unwrap();
+
+    assert_eq!(
+      std::fs::read_to_string(
+        root.path().join("etc/sysctl.d/99-rusty-sailor.conf")
+      ).unwrap(),
+      "net.ipv4.ip_forward = 1\n"
+    );
+  }
+}
@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::InstallError;
+use crate::fs::{backup_path, FileAttributes};
+use crate::host::Host;
+use crate::vendored::ArtifactRequest;
+
+// Something, that a component has left on the node
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Artifact {
+  Directory { path: PathBuf },
+  File { path: PathBuf },
+  // Previous contents of overwritten file are kept in backup
+  ReplacedFile { path: PathBuf, backup: PathBuf },
+  Unit { name: String }
+}
+
+impl fmt::Display for Artifact {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Artifact::Directory { path } => write!(f, "directory {}", path.display()),
+      Artifact::File { path } => write!(f, "file {}", path.display()),
+      Artifact::ReplacedFile { path, backup } => write!(
+        f,
+        "file {} (backup {})",
+        path.display(),
+        backup.display()
+      ),
+      Artifact::Unit { name } => write!(f, "unit {}", name)
+    }
+  }
+}
+
+// Passes every call through to the wrapped host,
+// while keeping track of artifacts it has produced.
+// Only what did not exist before is tracked, so that
+// undoing artifacts never removes anything pre-existing
+pub struct TrackingHost {
+  inner: Box<dyn Host>,
+  artifacts: RefCell<Vec<Artifact>>
+}
+
+impl TrackingHost {
+  pub fn new(
+    inner: Box<dyn Host>
+  ) -> Self {
+    Self {
+      inner,
+      artifacts: RefCell::new(vec![])
+    }
+  }
+
+  // Changes done through the returned host are not tracked
+  pub fn untracked(&self) -> &dyn Host {
+    &*self.inner
+  }
+
+  pub fn take_artifacts(&self) -> Vec<Artifact> {
+    self.artifacts.replace(vec![])
+  }
+
+  fn _track(
+    &self,
+    artifact: Artifact
+  ) {
+    let mut artifacts = self.artifacts.borrow_mut();
+    if !artifacts.contains(&artifact) {
+      artifacts.push(artifact);
+    }
+  }
+
+  fn _tracked_file(
+    &self,
+    path: &Path
+  ) -> Option<Artifact> {
+    self.artifacts.borrow().iter().find(|artifact| match artifact {
+      Artifact::File { path: x } | Artifact::ReplacedFile { path: x, .. } => x == path,
+      _ => false
+    }).cloned()
+  }
+
+  // Returns artifact, which should be tracked
+  // once the file has been written
+  fn _prepare_overwrite(
+    &self,
+    path: &Path
+  ) -> Artifact {
+    match self.inner.path_exists(path) {
+      true => Artifact::ReplacedFile { path: path.to_path_buf(), backup: backup_path(path) },
+      false => Artifact::File { path: path.to_path_buf() }
+    }
+  }
+
+  // Unpacking does not keep backups on its own
+  fn _prepare_unpack(
+    &self,
+    path: &Path
+  ) -> Result<Option<Artifact>, InstallError> {
+    if self._tracked_file(path).is_some() {
+      return Ok(None)
+    }
+    match self._prepare_overwrite(path) {
+      Artifact::ReplacedFile { path, backup } => {
+        self.inner.copy_file(&path, &backup)?;
+        // Backup is there already, even if unpacking would fail
+        self._track(Artifact::ReplacedFile { path, backup });
+        Ok(None)
+      },
+      artifact => Ok(Some(artifact))
+    }
+  }
+
+  // Host keeps backup of every replaced file, but only the
+  // one of file from before installation is worth keeping
+  fn _write_tracked(
+    &self,
+    artifact: &Artifact,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError> {
+    let backup = backup_path(path);
+    match artifact {
+      Artifact::ReplacedFile { .. } => {
+        let original_backup = backup_path(&backup);
+        self.inner.rename_file(&backup, &original_backup)?;
+        self.inner.write_file(path, contents, attributes)?;
+        self.inner.rename_file(&original_backup, &backup)
+      },
+      _ => {
+        self.inner.write_file(path, contents, attributes)?;
+        self.inner.remove_file(&backup)
+      }
+    }
+  }
+}
+
+impl Host for TrackingHost {
+  fn root(&self) -> Option<&Path> {
+    self.inner.root()
+  }
+
+  fn create_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let mut missing = path.ancestors()
+      .take_while(|x| !self.inner.path_exists(x))
+      .map(|x| x.to_path_buf())
+      .collect::<Vec<PathBuf>>();
+    missing.reverse();
+    self.inner.create_dir_all(path)?;
+    for directory in missing {
+      self._track(Artifact::Directory { path: directory });
+    }
+    Ok(())
+  }
+
+  fn write_file(
+    &self,
+    path: &Path,
+    contents: &[u8],
+    attributes: FileAttributes
+  ) -> Result<(), InstallError> {
+    if let Some(artifact) = self._tracked_file(path) {
+      return self._write_tracked(&artifact, path, contents, attributes)
+    }
+    let artifact = self._prepare_overwrite(path);
+    self.inner.write_file(path, contents, attributes)?;
+    self._track(artifact);
+    Ok(())
+  }
+
+  fn read_file(
+    &self,
+    path: &Path
+  ) -> Result<Option<Vec<u8>>, InstallError> {
+    self.inner.read_file(path)
+  }
+
+  fn path_exists(
+    &self,
+    path: &Path
+  ) -> bool {
+    self.inner.path_exists(path)
+  }
+
+  fn copy_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    self.inner.copy_file(from, to)
+  }
+
+  fn rename_file(
+    &self,
+    from: &Path,
+    to: &Path
+  ) -> Result<(), InstallError> {
+    self.inner.rename_file(from, to)
+  }
+
+  fn remove_file(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    self.inner.remove_file(path)
+  }
+
+  fn remove_dir_all(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    self.inner.remove_dir_all(path)
+  }
+
+  fn unpack_archive(
+    &self,
+    artifact: &ArtifactRequest,
+    destination: &Path,
+    file_name_whitelist: Option<&HashSet<OsString>>
+  ) -> Result<(), InstallError> {
+    let mut file_names = file_name_whitelist.map_or_else(
+      Vec::new,
+      |names| names.iter().collect::<Vec<&OsString>>()
+    );
+    file_names.sort();
+
+    let mut artifacts = vec![];
+    if file_name_whitelist.is_none() && !self.inner.path_exists(destination) {
+      artifacts.push(Artifact::Directory { path: destination.to_path_buf() });
+    }
+    for file_name in file_names {
+      if let Some(artifact) = self._prepare_unpack(&destination.join(file_name))? {
+        artifacts.push(artifact);
+      }
+    }
+
+    self.inner.unpack_archive(artifact, destination, file_name_whitelist)?;
+    for artifact in artifacts {
+      self._track(artifact);
+    }
+    Ok(())
+  }
+
+  fn run_command(
+    &self,
+    program: &str,
+    args: &[&str]
+  ) -> Result<Output, InstallError> {
+    self.inner.run_command(program, args)
+  }
+
+  fn systemctl(
+    &self,
+    args: &[&str]
+  ) -> Result<(), InstallError> {
+    self.inner.systemctl(args)
+  }
+
+  fn reload_systemd(
+    &self
+  ) -> Result<(), InstallError> {
+    self.inner.reload_systemd()
+  }
+
+  fn enable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    let was_enabled = self.inner.is_unit_enabled(unit);
+    self.inner.enable_unit(unit)?;
+    if !was_enabled {
+      self._track(Artifact::Unit { name: unit.to_string() });
+    }
+    Ok(())
+  }
+
+  fn stop_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    self.inner.stop_unit(unit)
+  }
+
+  fn disable_unit(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    self.inner.disable_unit(unit)
+  }
+
+  fn is_unit_enabled(
+    &self,
+    unit: &str
+  ) -> bool {
+    self.inner.is_unit_enabled(unit)
+  }
+
+  fn check_unit_status(
+    &self,
+    unit: &str
+  ) -> Result<(), InstallError> {
+    self.inner.check_unit_status(unit)
+  }
+
+  fn lookup_user(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    self.inner.lookup_user(name)
+  }
+
+  fn lookup_group(
+    &self,
+    name: &str
+  ) -> Result<Option<u32>, InstallError> {
+    self.inner.lookup_group(name)
+  }
+}
+
+fn _undo(
+  host: &dyn Host,
+  artifact: &Artifact
+) -> Result<(), InstallError> {
+  match artifact {
+    Artifact::Directory { path } => host.remove_dir_all(path),
+    Artifact::File { path } => host.remove_file(path),
+    Artifact::ReplacedFile { path, backup } => host.rename_file(backup, path),
+    Artifact::Unit { name } => host.disable_unit(name)
+  }
+}
+
+// Undoes artifacts in reverse order of their creation,
+// returns the ones, which could not have been undone
+pub fn rollback(
+  host: &dyn Host,
+  artifacts: &[Artifact]
+) -> Vec<Artifact> {
+  let mut remaining = artifacts.iter().rev().filter(|artifact| {
+    match _undo(host, artifact) {
+      Ok(_) => false,
+      Err(e) => {
+        error!("Unable to undo {}: {}", artifact, e);
+        true
+      }
+    }
+  }).cloned().collect::<Vec<Artifact>>();
+  remaining.reverse();
+  remaining
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::ArtifactSource;
+  use crate::host::fake::FakeHost;
+  use super::*;
+
+  fn _file(path: &str) -> Artifact {
+    Artifact::File { path: PathBuf::from(path) }
+  }
+
+  #[test]
+  fn test_only_new_artifacts_are_tracked() {
+    let fake = FakeHost::new();
+    fake.create_dir_all(Path::new("/etc")).unwrap();
+    fake.enable_unit("containerd.service").unwrap();
+    let host = TrackingHost::new(Box::new(fake.clone()));
+
+    host.create_dir_all(Path::new("/etc/systemd/system")).unwrap();
+    host.write_file(Path::new("/etc/systemd/system/etcd.service"), b"", FileAttributes::PUBLIC).unwrap();
+    host.write_file(Path::new("/etc/systemd/system/etcd.service"), b"[Unit]", FileAttributes::PUBLIC).unwrap();
+    host.enable_unit("etcd.service").unwrap();
+    host.enable_unit("containerd.service").unwrap();
+    host.untracked().write_file(Path::new("/etc/systemd/system/x"), b"", FileAttributes::PUBLIC).unwrap();
+
+    assert_eq!(
+      host.take_artifacts(),
+      vec![
+        Artifact::Directory { path: PathBuf::from("/etc/systemd") },
+        Artifact::Directory { path: PathBuf::from("/etc/systemd/system") },
+        _file("/etc/systemd/system/etcd.service"),
+        Artifact::Unit { name: "etcd.service".to_string() }
+      ]
+    );
+    assert!(host.take_artifacts().is_empty());
+    assert!(!fake.path_exists(Path::new("/etc/systemd/system/etcd.service.bak")));
+    assert!(fake.state().enabled_units.contains("etcd.service"));
+  }
+
+  #[test]
+  fn test_rollback_restores_previous_state() {
+    let fake = FakeHost::new();
+    fake.create_dir_all(Path::new("/etc")).unwrap();
+    fake.write_file(Path::new("/etc/hosts"), b"127.0.0.1 localhost", FileAttributes::PUBLIC).unwrap();
+    let host = TrackingHost::new(Box::new(fake.clone()));
+
+    host.write_file(Path::new("/etc/hosts"), b"10.0.0.1 sloop", FileAttributes::PUBLIC).unwrap();
+    host.write_file(Path::new("/etc/hosts"), b"10.0.0.1 yacht", FileAttributes::PUBLIC).unwrap();
+    host.create_dir_all(Path::new("/opt/rusty-sailor/etcd")).unwrap();
+    host.unpack_archive(
+      &ArtifactRequest { source: &ArtifactSource::Embedded, name: "etcd", version: None },
+      Path::new("/opt/rusty-sailor/etcd"),
+      Some(&vec![OsString::from("etcd")].into_iter().collect())
+    ).unwrap();
+    host.enable_unit("etcd.service").unwrap();
+    assert_eq!(fake.file_contents(Path::new("/etc/hosts.bak")).unwrap(), "127.0.0.1 localhost");
+    assert!(!fake.path_exists(Path::new("/etc/hosts.bak.bak")));
+
+    let remaining = rollback(host.untracked(), &host.take_artifacts());
+    assert!(remaining.is_empty());
+    assert_eq!(fake.file_contents(Path::new("/etc/hosts")).unwrap(), "127.0.0.1 localhost");
+    assert!(!fake.path_exists(Path::new("/etc/hosts.bak")));
+    assert!(!fake.path_exists(Path::new("/opt")));
+    assert!(fake.state().enabled_units.is_empty());
+
+    // Failures are reported back, rest is still undone
+    let remaining = rollback(
+      host.untracked(),
+      &[_file("/etc/missing"), _file("/etc/hosts")]
+    );
+    assert_eq!(remaining, vec![_file("/etc/missing")]);
+    assert!(!fake.path_exists(Path::new("/etc/hosts")));
+  }
+}
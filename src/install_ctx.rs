@@ -1,36 +1,54 @@
+use std::rc::Rc;
+
+use log::warn;
 use openssl::pkey::{PKey, Private};
 use openssl::x509::X509;
 
 use crate::components::InstallStepResult;
 use crate::config::Settings;
+use crate::config::sources::ConfigSources;
 use crate::errors::InstallError;
-use crate::logging::init_logger;
+use crate::host::Host;
+use crate::host::tracking::TrackingHost;
+use crate::logging::{init_logger, LogOptions};
 
 pub struct InstallCtx {
   pub ca_private_key: Option<PKey<Private>>,
   pub ca_certificate: Option<X509>,
-  pub config: Settings
+  pub config: Settings,
+  // Shared, so that runner can still reach the host
+  // after a failed step has consumed the context
+  pub host: Rc<TrackingHost>
 }
 
 impl InstallCtx {
   pub fn new(
-    custom_cfg_path: &Option<&str>
+    config_sources: &ConfigSources,
+    host: Box<dyn Host>
   ) -> Result<Self, InstallError> {
-    let cfg = Settings::new(&custom_cfg_path)?;
+    let cfg = Settings::new(config_sources)?;
+    cfg.validate()?;
     Ok(
       InstallCtx {
         ca_private_key: None,
         ca_certificate: None,
-        config: cfg
+        config: cfg,
+        host: Rc::new(TrackingHost::new(host))
       }
     )
   }
 
   pub fn new_with_init(
-    custom_cfg_path: &Option<&str>
+    config_sources: &ConfigSources,
+    log_options: &LogOptions,
+    host: Box<dyn Host>
   ) -> InstallStepResult {
-    let ctx = InstallCtx::new(custom_cfg_path)?;
-    init_logger(&ctx.config)?;
+    let ctx = InstallCtx::new(config_sources, host)?;
+    init_logger(&ctx.config, ctx.host.root(), log_options)?;
+    // Logger is not there yet, while the configuration is read
+    for warning in config_sources.warnings()? {
+      warn!("{}", warning);
+    }
     Ok(ctx)
   }
 }
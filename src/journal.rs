@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+use crate::errors::InstallError;
+use crate::fs::FileAttributes;
+use crate::host::Host;
+use crate::host::tracking::Artifact;
+
+const JOURNAL_FILE_NAME: &str = "journal.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+  Completed,
+  Failed
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JournalEntry {
+  pub status: StepStatus,
+  pub inputs_hash: String,
+  pub artifacts: Vec<Artifact>
+}
+
+// State of previous installer runs, kept under installation_dir,
+// so that an interrupted installation can be resumed
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Journal {
+  pub components: BTreeMap<String, JournalEntry>
+}
+
+impl Journal {
+  pub fn path(
+    settings: &Settings
+  ) -> PathBuf {
+    Path::new(&settings.installation_dir).join(JOURNAL_FILE_NAME)
+  }
+
+  // Unreadable journal is as good as no journal,
+  // everything will simply be installed again
+  pub fn load(
+    host: &dyn Host,
+    path: &Path
+  ) -> Result<Self, InstallError> {
+    let contents = match host.read_file(path)? {
+      Some(contents) => contents,
+      None => return Ok(Journal::default())
+    };
+    match serde_json::from_slice(&contents) {
+      Ok(journal) => Ok(journal),
+      Err(e) => {
+        warn!("Journal `{}` is corrupted and will be ignored: {}", path.display(), e);
+        Ok(Journal::default())
+      }
+    }
+  }
+
+  pub fn save(
+    &self,
+    host: &dyn Host,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    if let Some(parent) = path.parent() {
+      host.create_dir_all(parent)?;
+    }
+    host.write_file(path, &serde_json::to_vec_pretty(self)?, FileAttributes::PUBLIC)
+  }
+
+  pub fn is_completed(
+    &self,
+    name: &str,
+    inputs_hash: &str
+  ) -> bool {
+    self.components.get(name).is_some_and(
+      |entry| entry.status == StepStatus::Completed && entry.inputs_hash == inputs_hash
+    )
+  }
+
+  pub fn record(
+    &mut self,
+    name: &str,
+    entry: JournalEntry
+  ) {
+    self.components.insert(name.to_string(), entry);
+  }
+}
+
+pub fn hash_inputs(
+  inputs: &serde_json::Value
+) -> Result<String, InstallError> {
+  let digest = sha256(&serde_json::to_vec(inputs)?);
+  Ok(digest.iter().map(|x| format!("{:02x}", x)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::host::fake::FakeHost;
+  use super::*;
+
+  #[test]
+  fn test_journal_roundtrip() {
+    let host = FakeHost::new();
+    let path = Path::new("/opt/rusty-sailor/journal.json");
+    assert!(Journal::load(&host, path).unwrap().components.is_empty());
+
+    let mut journal = Journal::default();
+    journal.record(
+      "ca",
+      JournalEntry {
+        status: StepStatus::Completed,
+        inputs_hash: "abc".to_string(),
+        artifacts: vec![Artifact::Unit { name: "etcd.service".to_string() }]
+      }
+    );
+    journal.save(&host, path).unwrap();
+
+    let journal = Journal::load(&host, path).unwrap();
+    assert!(journal.is_completed("ca", "abc"));
+    assert!(!journal.is_completed("ca", "def"));
+    assert!(!journal.is_completed("etcd", "abc"));
+
+    host.write_file(path, b"{ not json", FileAttributes::PUBLIC).unwrap();
+    assert!(Journal::load(&host, path).unwrap().components.is_empty());
+  }
+}
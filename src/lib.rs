@@ -1,10 +1,17 @@
+pub mod commands;
 pub mod config;
 pub mod components;
 pub mod errors;
 pub mod fs;
+pub mod host;
 pub mod install_ctx;
+pub mod journal;
 pub mod logging;
 pub mod net;
 pub mod pki;
+pub mod preflight;
+pub mod process;
+pub mod progress;
+pub mod report;
 pub mod templates;
 pub mod vendored;
@@ -1,48 +1,311 @@
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-use simplelog::{
-  CombinedLogger,
-  ConfigBuilder,
-  LevelFilter,
-  SimpleLogger,
-  WriteLogger
-};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
 
-use crate::config::Settings;
+use crate::config::{LogFileMode, LogFormat, LoggingSettings, Settings};
 use crate::errors::InstallError;
+use crate::fs::rooted;
+use crate::progress;
 
-pub fn init_logger(cfg: &Settings) -> Result<(), InstallError> {
-  let log_level = match cfg.debug {
+const LOG_FILE_MODE: u32 = 0o640;
+
+// Logging related flags given on command line
+#[derive(Clone, Copy, Debug)]
+pub struct LogOptions {
+  // Terminal is set independently of the log file
+  pub terminal_level: LevelFilter,
+  pub to_file: bool,
+  // Component list with spinners replaces
+  // info lines on the terminal
+  pub progress: bool
+}
+
+impl Default for LogOptions {
+  fn default() -> Self {
+    Self {
+      terminal_level: LevelFilter::Info,
+      to_file: true,
+      progress: false
+    }
+  }
+}
+
+struct FileSink {
+  file: Mutex<File>,
+  format: LogFormat,
+  level: LevelFilter,
+  // Sorted from the most specific module
+  modules: Vec<(String, LevelFilter)>
+}
+
+impl FileSink {
+  fn level_of(
+    &self,
+    target: &str
+  ) -> LevelFilter {
+    self.modules
+      .iter()
+      .find(|(module, _)| {
+        target == module || target.starts_with(&format!("{}::", module))
+      })
+      .map_or(self.level, |(_, level)| *level)
+  }
+
+  fn max_level(&self) -> LevelFilter {
+    self.modules
+      .iter()
+      .map(|(_, level)| *level)
+      .fold(self.level, Ord::max)
+  }
+}
+
+struct Logger {
+  terminal_level: LevelFilter,
+  file: Option<FileSink>
+}
+
+fn _format_text(
+  record: &Record,
+  timestamp: &str
+) -> String {
+  let location = match (record.level(), record.file(), record.line()) {
+    (Level::Error, Some(file), Some(line)) => format!(" ({}:{})", file, line),
+    _ => String::new()
+  };
+  format!(
+    "{} [{}] {}: {}{}",
+    timestamp,
+    record.level(),
+    record.target(),
+    record.args(),
+    location
+  )
+}
+
+fn _format_json(
+  record: &Record,
+  timestamp: &str
+) -> String {
+  json!({
+    "timestamp": timestamp,
+    "level": record.level().to_string(),
+    "target": record.target(),
+    "message": record.args().to_string(),
+    "file": record.file(),
+    "line": record.line()
+  }).to_string()
+}
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.terminal_level
+      || self.file.as_ref().is_some_and(
+        |sink| metadata.level() <= sink.level_of(metadata.target())
+      )
+  }
+
+  fn log(&self, record: &Record) {
+    if record.level() <= self.terminal_level {
+      let line = format!("[{}] {}", record.level(), record.args());
+      match record.level() {
+        Level::Error | Level::Warn => if !progress::print_above(&line) {
+          eprintln!("{}", line)
+        },
+        _ => if !progress::is_view_enabled() {
+          println!("{}", line)
+        }
+      }
+    }
+
+    let sink = match &self.file {
+      Some(sink) if record.level() <= sink.level_of(record.target()) => sink,
+      _ => return
+    };
+    let timestamp = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    let line = match sink.format {
+      LogFormat::Text => _format_text(record, &timestamp),
+      LogFormat::Json => _format_json(record, &timestamp)
+    };
+    if let Ok(mut file) = sink.file.lock() {
+      let _ = writeln!(file, "{}", line);
+    }
+  }
+
+  fn flush(&self) {
+    if let Some(sink) = &self.file {
+      if let Ok(mut file) = sink.file.lock() {
+        let _ = file.flush();
+      }
+    }
+  }
+}
+
+fn _numbered_path(
+  path: &Path,
+  number: u32
+) -> PathBuf {
+  let mut numbered = path.as_os_str().to_os_string();
+  numbered.push(format!(".{}", number));
+  PathBuf::from(numbered)
+}
+
+// Shifts previous logs, so that the newest one becomes <file>.1
+// and the ones over `keep_files` are removed
+fn _rotate(
+  path: &Path,
+  keep_files: u32
+) -> Result<(), InstallError> {
+  if !path.exists() {
+    return Ok(())
+  }
+  let oldest = _numbered_path(path, keep_files);
+  if oldest.exists() {
+    fs::remove_file(&oldest)?;
+  }
+  for number in (1..keep_files).rev() {
+    let numbered = _numbered_path(path, number);
+    if numbered.exists() {
+      fs::rename(&numbered, _numbered_path(path, number + 1))?;
+    }
+  }
+  fs::rename(path, _numbered_path(path, 1))?;
+  Ok(())
+}
+
+// Log is appended to while the installer runs, so unlike
+// other files it can not be written with fs::write_atomic
+fn _open_log_file(
+  path: &Path,
+  logging: &LoggingSettings
+) -> Result<File, InstallError> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  if logging.file_mode == LogFileMode::Rotate {
+    _rotate(path, logging.keep_files)?;
+  }
+  Ok(
+    OpenOptions::new()
+      .create(true)
+      .append(true)
+      .mode(LOG_FILE_MODE)
+      .open(path)?
+  )
+}
+
+// Log file lives under `--root` too, like everything else the installer writes
+fn _file_sink(
+  cfg: &Settings,
+  root: Option<&Path>
+) -> Result<FileSink, InstallError> {
+  let level = match cfg.debug {
     true => LevelFilter::Trace,
-    false => LevelFilter::Info
+    false => cfg.logging.level.to_level_filter()
   };
+  let mut modules = cfg.logging.modules
+    .iter()
+    .map(|(module, level)| (module.clone(), level.to_level_filter()))
+    .collect::<Vec<(String, LevelFilter)>>();
+  modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
 
-  let logger_config = ConfigBuilder::new()
-    .set_target_level(LevelFilter::Off)  
-    .set_thread_level(LevelFilter::Off)
-    // Possible bug, see: https://github.com/Drakulix/simplelog.rs/issues/66
-    // In future, only Errors should have location
-    .set_location_level(LevelFilter::Error)
-    .set_time_format_str("%+")
-    .build();
-
-  let terminal_logger = SimpleLogger::new(
-    log_level,
-    logger_config.clone()
-  );
+  Ok(
+    FileSink {
+      file: Mutex::new(_open_log_file(&rooted(root, &cfg.log_file_path()), &cfg.logging)?),
+      format: cfg.logging.format,
+      level,
+      modules
+    }
+  )
+}
 
-  let logger_file = File::create("rusty-sailor.log")?;
-  let file_logger = WriteLogger::new(
-    log_level,
-    logger_config,
-    logger_file
+pub fn init_logger(
+  cfg: &Settings,
+  root: Option<&Path>,
+  options: &LogOptions
+) -> Result<(), InstallError> {
+  let file = match options.to_file {
+    true => Some(_file_sink(cfg, root)?),
+    false => None
+  };
+  let max_level = file.as_ref().map_or(
+    options.terminal_level,
+    |sink| sink.max_level().max(options.terminal_level)
   );
 
-  let _ = CombinedLogger::init(
-    vec![
-      terminal_logger,
-      file_logger
-    ]
+  log::set_boxed_logger(
+    Box::new(
+      Logger {
+        terminal_level: options.terminal_level,
+        file
+      }
+    )
   )?;
+  log::set_max_level(max_level);
+  if options.progress {
+    progress::enable_view();
+  }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+  use crate::config::LogLevel;
+  use super::*;
+
+  #[test]
+  fn test_module_levels() {
+    let mut modules = BTreeMap::new();
+    modules.insert("rusty_sailor::components".to_string(), LogLevel::Warn);
+    modules.insert("rusty_sailor::components::etcd".to_string(), LogLevel::Trace);
+    let root = tempfile::TempDir::new().unwrap();
+    let cfg = Settings {
+      logging: LoggingSettings { modules, ..Default::default() },
+      ..Default::default()
+    };
+    let sink = _file_sink(&cfg, Some(root.path())).unwrap();
+
+    assert_eq!(sink.level_of("rusty_sailor::components::etcd"), LevelFilter::Trace);
+    assert_eq!(sink.level_of("rusty_sailor::components::ca"), LevelFilter::Warn);
+    assert_eq!(sink.level_of("rusty_sailor::components_extra"), LevelFilter::Info);
+    assert_eq!(sink.level_of("rusty_sailor::pki"), LevelFilter::Info);
+    assert_eq!(sink.max_level(), LevelFilter::Trace);
+    assert!(rooted(Some(root.path()), &cfg.log_file_path()).exists());
+  }
+
+  #[test]
+  fn test_logs_are_rotated() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("rusty-sailor.log");
+
+    for run in 0..4 {
+      fs::write(&path, format!("run {}", run)).unwrap();
+      _rotate(&path, 2).unwrap();
+    }
+    assert!(!path.exists());
+    assert_eq!(fs::read_to_string(_numbered_path(&path, 1)).unwrap(), "run 3");
+    assert_eq!(fs::read_to_string(_numbered_path(&path, 2)).unwrap(), "run 2");
+    assert!(!_numbered_path(&path, 3).exists());
+  }
+
+  #[test]
+  fn test_json_format() {
+    let record = Record::builder()
+      .args(format_args!("etcd is up"))
+      .level(Level::Info)
+      .target("rusty_sailor::components::etcd")
+      .build();
+    let line: serde_json::Value = serde_json::from_str(
+      &_format_json(&record, "2020-11-21T10:00:00.000Z")
+    ).unwrap();
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["message"], "etcd is up");
+    assert_eq!(line["target"], "rusty_sailor::components::etcd");
+  }
+}
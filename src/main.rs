@@ -1,81 +1,418 @@
-use log::error;
+use std::path::PathBuf;
+
+use log::LevelFilter;
 
 use clap::{
-  crate_authors, crate_description, crate_name, crate_version, 
-  App, Arg
+  crate_authors, crate_description, crate_name, crate_version,
+  App, AppSettings, Arg, ArgMatches, SubCommand
 };
 
-use rusty_sailor::components::{InstallStepResult, run_steps};
-use rusty_sailor::components::etcd::etcd_component;
-use rusty_sailor::components::global_validation::global_validation_component;
-use rusty_sailor::install_ctx::InstallCtx;
+use rusty_sailor::commands::{self, GlobalOptions};
+use rusty_sailor::commands::install::{parse_peer, InstallOptions, JoinOptions};
+use rusty_sailor::commands::reset::ResetCommandOptions;
+use rusty_sailor::components::reset::ResetOptions;
+use rusty_sailor::components::runner::{ComponentSelection, RunOptions};
+use rusty_sailor::config::EtcdNode;
+use rusty_sailor::config::sources::{parse_override, ConfigSources};
+use rusty_sailor::errors::InstallError;
+use rusty_sailor::logging::LogOptions;
+use rusty_sailor::progress::is_terminal;
+
+// Kept in sync with ErrorKind::exit_code
+const EXIT_CODES_HELP: &str = "EXIT CODES:
+     0  Success
+     1  Other failure
+    10  Invalid configuration
+    11  Custom CA was not provided
+    12  Invalid bind address
+    20  Preflight check has failed
+    30  File could not be read or written
+    31  Vendored archive could not be unpacked
+    32  Template could not be rendered
+    33  Serialization has failed
+    34  Logger could not be set up
+    40  OpenSSL operation has failed
+    50  External command could not be run
+    51  systemd operation has failed
+    52  etcd operation has failed
+    60  Invalid component selection";
+
+// Flags shared by install and join
+fn component_args<'a, 'b>(
+  subcommand: App<'a, 'b>,
+  is_custom_ca_required: bool
+) -> App<'a, 'b> {
+  subcommand
+    .arg(
+      Arg::with_name("ca_pkey")
+        .long("ca-private-key")
+        .takes_value(true)
+        .required(is_custom_ca_required)
+        .requires("ca_cert")
+        .help("Path to ca private key that should be used"),
+    )
+    .arg(
+      Arg::with_name("ca_cert")
+        .long("ca-certificate")
+        .takes_value(true)
+        .required(is_custom_ca_required)
+        .requires("ca_pkey")
+        .help("Path to ca certificate that should be used"),
+    )
+    .arg(
+      Arg::with_name("fix")
+        .long("fix")
+        .takes_value(false)
+        .help("Attempt to fix failing host prerequisites (kernel modules, sysctls)"),
+    )
+    .arg(
+      Arg::with_name("only")
+        .long("only")
+        .takes_value(true)
+        .multiple(true)
+        .use_delimiter(true)
+        .help("Install only given components (and their dependencies)"),
+    )
+    .arg(
+      Arg::with_name("skip")
+        .long("skip")
+        .takes_value(true)
+        .multiple(true)
+        .use_delimiter(true)
+        .help("Do not install given components"),
+    )
+    .arg(
+      Arg::with_name("force")
+        .long("force")
+        .takes_value(true)
+        .multiple(true)
+        .use_delimiter(true)
+        .help("Install given components again, even if previous run has completed them"),
+    )
+    .arg(
+      Arg::with_name("keep_on_failure")
+        .long("keep-on-failure")
+        .takes_value(false)
+        .help("Do not roll back changes of a failed component"),
+    )
+    .arg(
+      Arg::with_name("report")
+        .long("report")
+        .takes_value(true)
+        .value_name("FILE")
+        .help("Writes JSON report of the run (components, certificates, endpoints, error)"),
+    )
+}
+
+fn values_of(
+  matches: &ArgMatches,
+  name: &str
+) -> Vec<String> {
+  matches.values_of(name).map_or_else(
+    Vec::new,
+    |values| values.map(String::from).collect()
+  )
+}
+
+fn install_options(
+  matches: &ArgMatches
+) -> InstallOptions {
+  InstallOptions {
+    custom_ca_pkey_path: matches.value_of("ca_pkey").map(String::from),
+    custom_ca_cert_path: matches.value_of("ca_cert").map(String::from),
+    should_fix: matches.is_present("fix"),
+    selection: ComponentSelection {
+      only: values_of(matches, "only"),
+      skip: values_of(matches, "skip"),
+      force: values_of(matches, "force")
+    },
+    run_options: RunOptions {
+      keep_on_failure: matches.is_present("keep_on_failure"),
+      report_path: matches.value_of("report").map(PathBuf::from)
+    }
+  }
+}
+
+fn join(
+  global: &GlobalOptions,
+  matches: &ArgMatches
+) -> Result<(), InstallError> {
+  let peers = values_of(matches, "peer")
+    .iter()
+    .map(|x| parse_peer(x))
+    .collect::<Result<Vec<EtcdNode>, InstallError>>()?;
+  commands::install::join(
+    global,
+    &JoinOptions {
+      peers,
+      install: install_options(matches)
+    }
+  )
+}
+
+fn terminal_level(
+  matches: &ArgMatches
+) -> LevelFilter {
+  match (matches.is_present("quiet"), matches.occurrences_of("verbose")) {
+    (true, _) => LevelFilter::Error,
+    (false, 0) => LevelFilter::Info,
+    (false, 1) => LevelFilter::Debug,
+    _ => LevelFilter::Trace
+  }
+}
+
+fn global_options(
+  matches: &ArgMatches
+) -> Result<GlobalOptions, InstallError> {
+  let overrides = values_of(matches, "set")
+    .iter()
+    .map(|x| parse_override(x))
+    .collect::<Result<Vec<(String, String)>, InstallError>>()?;
+  Ok(
+    GlobalOptions {
+      config: ConfigSources::standard(
+        matches.value_of("config").map(String::from),
+        overrides
+      )?,
+      log: LogOptions {
+        terminal_level: terminal_level(matches),
+        // Dry run must not leave anything behind, log file included
+        to_file: !matches.is_present("dry_run"),
+        // Plain lines, whenever output may be read by something else
+        // than a person, or has lines of its own (planned changes).
+        // Only installation reports progress of components
+        progress: matches!(matches.subcommand_name(), Some("install") | Some("join"))
+          && terminal_level(matches) == LevelFilter::Info
+          && !matches.is_present("dry_run")
+          && is_terminal()
+      },
+      root: matches.value_of("root").map(PathBuf::from),
+      dry_run: matches.is_present("dry_run")
+    }
+  )
+}
+
+fn run(
+  global: &GlobalOptions,
+  matches: &ArgMatches
+) -> Result<(), InstallError> {
+  match matches.subcommand() {
+    ("install", Some(m)) => commands::install::install(global, &install_options(m)),
+    ("join", Some(m)) => join(global, m),
+    ("reset", Some(m)) => commands::reset::reset(
+      global,
+      &ResetCommandOptions {
+        assume_yes: m.is_present("yes"),
+        reset: ResetOptions {
+          wipe_data: m.is_present("wipe_data"),
+          wipe_pki: m.is_present("wipe_pki")
+        }
+      }
+    ),
+    ("status", _) => commands::status::status(global),
+    ("certs", _) => commands::certs::certs(global),
+    ("etcd", Some(m)) => commands::etcd::etcd(
+      global,
+      &m.values_of("args").map_or_else(Vec::new, |x| x.collect::<Vec<&str>>())
+    ),
+    ("config", Some(m)) => match m.subcommand() {
+      ("show", Some(m)) => commands::config::show(global, m.is_present("effective")),
+      ("validate", _) => commands::config::validate(global),
+      ("print-defaults", _) => commands::config::print_defaults(),
+      ("schema", _) => commands::config::schema(),
+      ("migrate", Some(m)) => commands::config::migrate(global, m.is_present("in_place")),
+      _ => unreachable!()
+    },
+    ("vendored", Some(m)) => match m.subcommand() {
+      ("list", _) => commands::vendored::list(),
+      _ => unreachable!()
+    },
+    ("version", _) => {
+      println!("{}", commands::version());
+      Ok(())
+    },
+    _ => unreachable!()
+  }
+}
 
 fn main() {
   let matches = App::new(crate_name!())
     .version(crate_version!())
     .author(crate_authors!())
     .about(crate_description!())
-    .arg(
-      Arg::with_name("version")
-        .long("version")
-        .takes_value(false)
-        .help("Prints current rusty-sailor version"),
-    )
+    .setting(AppSettings::SubcommandRequiredElseHelp)
+    .setting(AppSettings::VersionlessSubcommands)
+    .after_help(EXIT_CODES_HELP)
     .arg(
       Arg::with_name("config")
         .long("config")
         .short("c")
         .takes_value(true)
         .required(false)
-        .help("Path to configuration file which should be used"),
+        .global(true)
+        .help("Configuration file (toml, yaml or json), applied over /etc/rusty-sailor/config.toml, `-` reads stdin"),
     )
     .arg(
-      Arg::with_name("ca_pkey")
-        .long("ca-private-key")
+      Arg::with_name("set")
+        .long("set")
         .takes_value(true)
-        .required(false)
-        .requires("ca_cert")
-        .help("Path to ca private key that should be used"),
+        .multiple(true)
+        .number_of_values(1)
+        .global(true)
+        .help("Overrides configuration value, as key=value (e.g. etcd.listen_client_port=2479)"),
     )
     .arg(
-      Arg::with_name("ca_cert")
-        .long("ca-certificate")
+      Arg::with_name("root")
+        .long("root")
         .takes_value(true)
         .required(false)
-        .requires("ca_pkey")
-        .help("Path to ca certificate that should be used"),
+        .global(true)
+        .help("Install into directory with mounted image, instead of the running system"),
+    )
+    .arg(
+      Arg::with_name("quiet")
+        .long("quiet")
+        .short("q")
+        .takes_value(false)
+        .global(true)
+        .conflicts_with("verbose")
+        .help("Prints only errors on the terminal, log file is not affected"),
+    )
+    .arg(
+      Arg::with_name("verbose")
+        .short("v")
+        .multiple(true)
+        .takes_value(false)
+        .global(true)
+        .help("Prints more on the terminal (-v for debug, -vv for trace), log file is not affected"),
+    )
+    .arg(
+      Arg::with_name("dry_run")
+        .long("dry-run")
+        .takes_value(false)
+        .global(true)
+        .help("Print every planned change, without applying any of them"),
+    )
+    .subcommand(
+      component_args(
+        SubCommand::with_name("install")
+          .about("Installs every component on the node"),
+        false
+      )
+    )
+    .subcommand(
+      component_args(
+        SubCommand::with_name("join")
+          .about("Installs the node as a new member of existing cluster"),
+        true
+      )
+      .arg(
+        Arg::with_name("peer")
+          .long("peer")
+          .takes_value(true)
+          .multiple(true)
+          .number_of_values(1)
+          .help("Member of existing cluster, as name=peer_url (overrides etcd.other_nodes)"),
+      )
+    )
+    .subcommand(
+      SubCommand::with_name("reset")
+        .about("Removes everything, that installer has put on the node")
+        .arg(
+          Arg::with_name("yes")
+            .long("yes")
+            .short("y")
+            .takes_value(false)
+            .help("Do not ask for confirmation"),
+        )
+        .arg(
+          Arg::with_name("wipe_data")
+            .long("wipe-data")
+            .takes_value(false)
+            .help("Remove etcd data_dir as well"),
+        )
+        .arg(
+          Arg::with_name("wipe_pki")
+            .long("wipe-pki")
+            .takes_value(false)
+            .help("Remove CA and the rest of installation dir as well"),
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("status")
+        .about("Shows state of installed components and their units")
+    )
+    .subcommand(
+      SubCommand::with_name("certs")
+        .about("Lists installed certificates and their expiry")
+    )
+    .subcommand(
+      SubCommand::with_name("etcd")
+        .about("Runs etcdctl against the local member, e.g. `etcd -- member list`")
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+          Arg::with_name("args")
+            .multiple(true)
+            .allow_hyphen_values(true)
+            .help("Arguments passed through to etcdctl"),
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("config")
+        .about("Inspects installer configuration")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("show")
+            .about("Prints configuration, as seen by the installer")
+            .arg(
+              Arg::with_name("effective")
+                .long("effective")
+                .takes_value(false)
+                .help("Prints every value along with its source"),
+            )
+        )
+        .subcommand(
+          SubCommand::with_name("validate")
+            .about("Checks configuration and reports every error found")
+        )
+        .subcommand(
+          SubCommand::with_name("print-defaults")
+            .about("Prints full default configuration, with comments")
+        )
+        .subcommand(
+          SubCommand::with_name("schema")
+            .about("Prints JSON Schema of the configuration file")
+        )
+        .subcommand(
+          SubCommand::with_name("migrate")
+            .about("Upgrades --config file to the current api_version")
+            .arg(
+              Arg::with_name("in_place")
+                .long("in-place")
+                .takes_value(false)
+                .help("Overwrites the file, instead of printing the result"),
+            )
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("vendored")
+        .about("Inspects archives embedded in the binary")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("list")
+            .about("Prints embedded archives with their versions and sha256 digests")
+        )
+    )
+    .subcommand(
+      SubCommand::with_name("version")
+        .about("Prints current rusty-sailor version")
     )
     .get_matches();
 
-  if matches.is_present("version") {
-    println!(crate_version!());
-    std::process::exit(0);
-  };
-
-  let ca_pkey_path = matches.value_of("ca_pkey");
-  let ca_cert_path = matches.value_of("ca_cert");
-  let custom_config_path = matches.value_of("config");
+  let result = global_options(&matches).and_then(|global| run(&global, &matches));
 
-  let ca_component = rusty_sailor::components::ca::create_ca_component(
-    &ca_pkey_path,
-    &ca_cert_path
-  );
-
-  let install_components: Vec<&Fn(InstallCtx) -> InstallStepResult> = vec![
-    &global_validation_component,
-    &ca_component,
-    &etcd_component,
-  ];
-  
-  match run_steps(
-    InstallCtx::new_with_init(&custom_config_path),
-    install_components
-  ) {
-    Ok(_) => std::process::exit(0),
-    Err(e) => {
-      error!("Installer execution has ended with a failure!");
-      error!("Error details: '{}'", e.to_string());
-      std::process::exit(1)
-    }
-  };
+  if let Err(e) = result {
+    eprintln!("Error: {}", e.report());
+    std::process::exit(e.exit_code())
+  }
 }
@@ -1,16 +1,29 @@
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::process::Command;
+use std::time::Duration;
 
+use crate::process;
 
-pub fn guess_node_hostname() -> Option<String> {
-  let output = Command::new("hostname").args(&["--fqdn"]).output().ok()?;
+const HOSTNAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Output of `hostname` with given flag, if it succeeded
+fn _hostname(
+  flag: &str
+) -> Option<String> {
+  let output = process::run("hostname", &[flag], HOSTNAME_TIMEOUT).ok()?;
+  if !output.status.success() {
+    return None
+  }
   let stdout = String::from_utf8(output.stdout).ok()?;
-  Some(stdout)
+  Some(stdout.trim().to_string())
+}
+
+pub fn guess_node_hostname() -> Option<String> {
+  _hostname("--fqdn")
 }
 
 pub fn guess_node_ip() -> Option<IpAddr> {
-  let output = Command::new("hostname").args(&["-i"]).output().ok()?;
-  let stdout = String::from_utf8(output.stdout).ok()?;
+  let stdout = _hostname("-i")?;
+  let stdout = stdout.as_str();
 
   let ip_addr_v4 = stdout.parse::<Ipv4Addr>();
   let ip_addr_v6 = stdout.parse::<Ipv6Addr>();
@@ -6,7 +6,7 @@ use openssl::nid::Nid;
 use openssl::pkey::{PKey, Private};
 use openssl::rsa::Rsa;
 use openssl::x509::{
-  X509, X509Name, X509Req, X509ReqBuilder
+  X509, X509Name, X509NameRef, X509Req, X509ReqBuilder
 };
 use openssl::x509::extension::{
   AuthorityKeyIdentifier, BasicConstraints, KeyUsage, 
@@ -14,6 +14,29 @@ use openssl::x509::extension::{
 };
 
 use crate::config;
+use crate::progress;
+
+// First common name entry, `-` if there is none
+pub fn common_name(
+  name: &X509NameRef
+) -> String {
+  name.entries_by_nid(Nid::COMMONNAME)
+    .next()
+    .map_or_else(
+      || "-".to_string(),
+      |x| String::from_utf8_lossy(x.data().as_slice()).to_string()
+    )
+}
+
+// Takes seconds for 4096 bits
+fn _generate_rsa_key(
+  bits: u32
+) -> Result<Rsa<Private>, ErrorStack> {
+  progress::task(
+    &format!("Generating {}-bit RSA key", bits),
+    || Rsa::generate(bits)
+  )
+}
 
 fn _create_cert_name(
   settings: &config::PkiSettings,
@@ -51,7 +74,7 @@ fn _create_csr(
   common_name: &str
 ) -> Result<X509Req, ErrorStack> {
   let mut csr = X509ReqBuilder::new()?;
-  csr.set_pubkey(&private_key)?;
+  csr.set_pubkey(private_key)?;
 
   let name = _create_cert_name(
     settings,
@@ -59,7 +82,7 @@ fn _create_csr(
   )?;
 
   csr.set_subject_name(&name)?;
-  csr.sign(&private_key, MessageDigest::sha256())?;
+  csr.sign(private_key, MessageDigest::sha256())?;
   let csr = csr.build();
   Ok(csr)
 }
@@ -68,7 +91,7 @@ pub fn create_ca_certificate(
   settings: &config::PkiSettings
 )-> Result<(PKey<Private>, X509), ErrorStack> {
   let private_key = PKey::from_rsa(
-    Rsa::generate(settings.rsa_size)?
+    _generate_rsa_key(settings.rsa_size)?
   )?;
 
   let ca_name = _create_cert_name(
@@ -137,13 +160,13 @@ pub fn create_ca_signed_certificate(
   alt_names_ip: &Option<Vec<String>>
 ) -> Result<(PKey<Private>, X509), ErrorStack> {
   let private_key = PKey::from_rsa(
-    Rsa::generate(settings.rsa_size)?
+    _generate_rsa_key(settings.rsa_size)?
   )?;
 
   let csr = _create_csr(
-    &settings,
+    settings,
     &private_key,
-    &common_name
+    common_name
   )?;
 
   // There has to be a better way
@@ -181,13 +204,13 @@ pub fn create_ca_signed_certificate(
   )?;
 
   let subject_key_identifier = SubjectKeyIdentifier::new()
-    .build(&cert.x509v3_context(Some(&ca_cert), None))?;
+    .build(&cert.x509v3_context(Some(ca_cert), None))?;
   cert.append_extension(subject_key_identifier)?;
 
   let authority_key_identifier = AuthorityKeyIdentifier::new()
     .keyid(true)
     .issuer(true)
-    .build(&cert.x509v3_context(Some(&ca_cert), None))?;
+    .build(&cert.x509v3_context(Some(ca_cert), None))?;
   cert.append_extension(authority_key_identifier)?;
 
   let mut san = SubjectAlternativeName::new();
@@ -198,11 +221,11 @@ pub fn create_ca_signed_certificate(
     alt_names.iter().for_each(|ip| { san.ip(ip); });
   }
   let san = san.build(
-    &cert.x509v3_context(Some(&ca_cert), None)
+    &cert.x509v3_context(Some(ca_cert), None)
   )?;
   cert.append_extension(san)?;
 
-  cert.sign(&ca_private_key, MessageDigest::sha256())?;
+  cert.sign(ca_private_key, MessageDigest::sha256())?;
   let cert = cert.build();
   Ok((private_key, cert))
 }
@@ -211,19 +234,20 @@ pub fn create_ca_signed_certificate(
 mod tests {
   use openssl::x509::store::X509StoreBuilder;
   use openssl::x509::X509StoreContext;
+  use crate::config::sources::ConfigSources;
   use crate::errors::InstallError;
   use super::*;
 
     #[test]
     fn test_cert_validation() -> Result<(), InstallError>{
-      match config::Settings::new(&None) {
+      match config::Settings::new(&ConfigSources::default()) {
         Ok(settings) => {
           let (ca_pkey, ca_cert) = create_ca_certificate(&settings.pki)?;
           let (_, cert) = create_ca_signed_certificate(
             &settings.pki,
             &ca_pkey,
             &ca_cert,
-            &"blackwood".to_string(),
+            "blackwood",
             &13,
             &Some(vec!["blackwood.local".to_string()]),
             &Some(vec!["127.0.0.1".to_string()])
@@ -1,28 +1,57 @@
 use std::fs::File;
-use std::io::prelude::{Read, Write};
+use std::io::prelude::Read;
 use std::path::Path;
 
 use openssl::pkey::{PKey, Private};
+use openssl::symm::Cipher;
 use openssl::x509::X509;
 
+use crate::config::secret::Secret;
 use crate::errors::InstallError;
+use crate::fs::{FileAttributes, Owner};
+use crate::host::Host;
 
 pub fn save_as_pem_private_key(
+  host: &dyn Host,
   key: &PKey<Private>,
-  filename: &Path
+  filename: &Path,
+  owner: Option<Owner>
 ) -> Result<(), InstallError> {
-  let mut file = File::create(filename)?;
-  file.write_all(&key.private_key_to_pem_pkcs8()?)?;
-  Ok(())
+  host.write_file(
+    filename,
+    &key.private_key_to_pem_pkcs8()?,
+    FileAttributes { owner, ..FileAttributes::PRIVATE }
+  )
+}
+
+pub fn save_as_encrypted_pem_private_key(
+  host: &dyn Host,
+  key: &PKey<Private>,
+  passphrase: &Secret,
+  filename: &Path,
+  owner: Option<Owner>
+) -> Result<(), InstallError> {
+  host.write_file(
+    filename,
+    &key.private_key_to_pem_pkcs8_passphrase(
+      Cipher::aes_256_cbc(),
+      passphrase.expose().as_bytes()
+    )?,
+    FileAttributes { owner, ..FileAttributes::PRIVATE }
+  )
 }
 
 pub fn save_as_pem_certificate(
+  host: &dyn Host,
   certificate: &X509,
-  filename: &Path
+  filename: &Path,
+  owner: Option<Owner>
 ) -> Result<(), InstallError> {
-  let mut file = File::create(filename)?;
-  file.write_all(&certificate.to_pem()?)?;
-  Ok(())
+  host.write_file(
+    filename,
+    &certificate.to_pem()?,
+    FileAttributes { owner, ..FileAttributes::PUBLIC }
+  )
 }
 
 pub fn load_pem_certificate(
@@ -35,12 +64,27 @@ pub fn load_pem_certificate(
   Ok(X509::from_pem(&pem_bytes)?)
 }
 
+pub fn private_key_from_pem(
+  pem_bytes: &[u8],
+  passphrase: Option<&Secret>
+) -> Result<PKey<Private>, InstallError> {
+  match passphrase {
+    Some(passphrase) => Ok(
+      PKey::private_key_from_pem_passphrase(pem_bytes, passphrase.expose().as_bytes())?
+    ),
+    // Empty passphrase, so that OpenSSL does not
+    // prompt on terminal for an encrypted key
+    None => Ok(PKey::private_key_from_pem_callback(pem_bytes, |_| Ok(0))?)
+  }
+}
+
 pub fn load_pem_private_key(
-  filepath: &str
+  filepath: &str,
+  passphrase: Option<&Secret>
 ) -> Result<PKey<Private>, InstallError> {
   let mut file = File::open(filepath)?;
   let mut pem_bytes = Vec::new();
   
   file.read_to_end(&mut  pem_bytes)?;
-  Ok(PKey::private_key_from_pem(&pem_bytes)?)
+  private_key_from_pem(&pem_bytes, passphrase)
 }
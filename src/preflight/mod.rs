@@ -0,0 +1,85 @@
+use log::{debug, error};
+
+use crate::errors::{ErrorKind, InstallError};
+
+pub mod network;
+pub mod system;
+
+// Collects outcomes of independent checks, so that
+// operator gets every problem at once, instead of
+// fixing them one re-run at a time.
+pub struct PreflightReport {
+  failures: Vec<(String, InstallError)>
+}
+
+impl PreflightReport {
+  pub fn new() -> Self {
+    Self {
+      failures: vec![]
+    }
+  }
+
+  pub fn record(
+    &mut self,
+    check_name: &str,
+    result: Result<(), InstallError>
+  ) {
+    match result {
+      Ok(_) => debug!("Preflight check '{}' has passed", check_name),
+      Err(e) => {
+        error!("Preflight check '{}' has failed: {}", check_name, e);
+        self.failures.push((check_name.to_string(), e));
+      }
+    }
+  }
+
+  pub fn is_ok(&self) -> bool {
+    self.failures.is_empty()
+  }
+
+  pub fn finish(self) -> Result<(), InstallError> {
+    if self.is_ok() {
+      return Ok(())
+    }
+
+    let summary = self.failures
+      .iter()
+      .map(|(name, e)| format!("  - {}: {}", name, e))
+      .collect::<Vec<String>>()
+      .join("\n");
+    Err(
+      InstallError::new(
+        ErrorKind::Preflight,
+        format!(
+          "{} preflight check(s) have failed:\n{}",
+          self.failures.len(),
+          summary
+        )
+      )
+    )
+  }
+}
+
+impl Default for PreflightReport {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_failures_are_reported_together() {
+    let mut report = PreflightReport::new();
+    report.record("first", Err(InstallError::new_from_str(ErrorKind::Other, "a")));
+    report.record("second", Ok(()));
+    report.record("third", Err(InstallError::new_from_str(ErrorKind::Other, "b")));
+
+    let error = report.finish().unwrap_err();
+    assert!(error.msg.starts_with("2 preflight check(s) have failed"));
+    assert!(error.msg.contains("first: a"));
+    assert!(error.msg.contains("third: b"));
+  }
+}
@@ -0,0 +1,175 @@
+use std::net::{
+  IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket
+};
+use std::time::Duration;
+
+use crate::config::{EtcdNode, Settings};
+use crate::errors::{ErrorKind, InstallError};
+use crate::preflight::PreflightReport;
+
+fn _bind_address_is_local(
+  bind_address: IpAddr
+) -> Result<(), InstallError> {
+  // Binding to an address, which is not assigned
+  // to any local interface fails with EADDRNOTAVAIL
+  UdpSocket::bind(SocketAddr::new(bind_address, 0)).map_or_else(
+    |e| Err(InstallError::new(
+      ErrorKind::BindAddress,
+      format!(
+        "`{}` is not assigned to any local network interface ({})",
+        bind_address,
+        e
+      )
+    )),
+    |_| Ok(())
+  )
+}
+
+// Ports of etcd, that has been set up by previous
+// run, are rightfully held by it already
+fn _component_ports(
+  settings: &Settings,
+  is_etcd_installed: bool
+) -> Vec<(&'static str, u16)> {
+  match is_etcd_installed {
+    true => vec![],
+    false => vec![
+      ("etcd client", settings.etcd.listen_client_port),
+      ("etcd peer", settings.etcd.listen_peer_port)
+    ]
+  }
+}
+
+fn _port_is_free(
+  bind_address: IpAddr,
+  port: u16
+) -> Result<(), InstallError> {
+  TcpListener::bind(SocketAddr::new(bind_address, port)).map_or_else(
+    |e| Err(InstallError::new(
+      ErrorKind::Preflight,
+      format!("Port `{}:{}` is not available ({})", bind_address, port, e)
+    )),
+    |_| Ok(())
+  )
+}
+
+fn _hostname_resolves_to(
+  hostname: &str,
+  bind_address: IpAddr
+) -> Result<(), InstallError> {
+  let resolved = (hostname, 0).to_socket_addrs().map_err(|e|
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Hostname `{}` could not be resolved ({})", hostname, e)
+    )
+  )?.map(|addr| addr.ip()).collect::<Vec<IpAddr>>();
+
+  if resolved.contains(&bind_address) {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!(
+        "Hostname `{}` resolves to {:?}, which does not include `{}`",
+        hostname,
+        resolved,
+        bind_address
+      )
+    )
+  )
+}
+
+fn _peer_is_reachable(
+  node: &EtcdNode,
+  timeout: Duration
+) -> Result<(), InstallError> {
+  let addresses = node.peer_url.socket_addrs(|| None).map_err(|e|
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Peer `{}` could not be resolved ({})", node.peer_url, e)
+    )
+  )?;
+
+  let mut last_error = None;
+  for address in addresses {
+    match TcpStream::connect_timeout(&address, timeout) {
+      Ok(_) => return Ok(()),
+      Err(e) => last_error = Some(e)
+    }
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!(
+        "Peer `{}` ({}) is not reachable over tcp ({})",
+        node.name,
+        node.peer_url,
+        last_error.map_or(
+          "no addresses".to_string(),
+          |e| e.to_string()
+        )
+      )
+    )
+  )
+}
+
+pub fn network_preflight(
+  settings: &Settings,
+  is_etcd_installed: bool
+) -> Result<(), InstallError> {
+  let mut report = PreflightReport::new();
+
+  report.record(
+    "bind address is local",
+    _bind_address_is_local(settings.bind_address)
+  );
+  for (component, port) in _component_ports(settings, is_etcd_installed) {
+    report.record(
+      &format!("{} port {} is free", component, port),
+      _port_is_free(settings.bind_address, port)
+    );
+  }
+  report.record(
+    "hostname resolves to bind address",
+    _hostname_resolves_to(&settings.hostname, settings.bind_address)
+  );
+
+  let other_nodes: Vec<EtcdNode> = vec![];
+  let other_nodes = settings.etcd.other_nodes.as_ref().unwrap_or(&other_nodes);
+  for node in other_nodes.iter() {
+    report.record(
+      &format!("peer {} is reachable", node.name),
+      _peer_is_reachable(node, settings.etcd.peer_connect_timeout)
+    );
+  }
+
+  report.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::net::Ipv4Addr;
+  use super::*;
+
+  #[test]
+  fn test_ports_of_installed_etcd_are_not_checked() {
+    let settings = Settings::default();
+    assert_eq!(
+      _component_ports(&settings, false),
+      vec![("etcd client", 2379), ("etcd peer", 2380)]
+    );
+    assert!(_component_ports(&settings, true).is_empty());
+  }
+
+  #[test]
+  fn test_taken_port_is_reported() {
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let listener = TcpListener::bind(SocketAddr::new(localhost, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(_port_is_free(localhost, port).is_err());
+    drop(listener);
+    assert!(_port_is_free(localhost, port).is_ok());
+  }
+}
@@ -0,0 +1,564 @@
+use std::env;
+use std::ffi::CString;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use askama::Template;
+use log::info;
+
+use crate::config::Settings;
+use crate::errors::{ErrorKind, InstallError};
+use crate::fs::rooted;
+use crate::host::Host;
+use crate::preflight::PreflightReport;
+use crate::templates::render_and_save;
+
+const REQUIRED_KERNEL_MODULES: [&str; 2] = ["br_netfilter", "overlay"];
+const REQUIRED_SYSCTLS: [&str; 2] = [
+  "net.ipv4.ip_forward",
+  "net.bridge.bridge-nf-call-iptables"
+];
+const MIN_KERNEL_VERSION: (u32, u32) = (4, 15);
+const MIN_FREE_DISK_SPACE_MB: u64 = 2048;
+pub const MODULES_LOAD_DROP_IN_PATH: &str = "/etc/modules-load.d/rusty-sailor.conf";
+pub const SYSCTL_DROP_IN_PATH: &str = "/etc/sysctl.d/99-rusty-sailor.conf";
+
+#[derive(Template)]
+#[template(path = "preflight/modules-load.conf", escape = "none")]
+struct ModulesLoadTemplate<'a> {
+  modules: &'a [&'a str]
+}
+
+#[derive(Template)]
+#[template(path = "preflight/sysctl.conf", escape = "none")]
+struct SysctlTemplate<'a> {
+  parameters: &'a [&'a str]
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CgroupVersion {
+  V1,
+  V2
+}
+
+// Location of kernel-provided pseudo filesystems,
+// overridable so that checks can be run against fixtures
+pub struct SystemPaths {
+  pub proc_root: PathBuf,
+  pub sys_root: PathBuf
+}
+
+impl Default for SystemPaths {
+  fn default() -> Self {
+    SystemPaths {
+      proc_root: PathBuf::from("/proc"),
+      sys_root: PathBuf::from("/sys")
+    }
+  }
+}
+
+fn _read_trimmed(
+  path: &Path
+) -> Result<String, InstallError> {
+  read_to_string(path).map(|x| x.trim().to_string()).map_err(|e|
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Could not read `{}` ({})", path.display(), e)
+    )
+  )
+}
+
+fn _is_kernel_module_loaded(
+  paths: &SystemPaths,
+  module: &str
+) -> bool {
+  // Built-in modules are not listed in /proc/modules,
+  // but they still show up under /sys/module
+  let is_listed = read_to_string(paths.proc_root.join("modules")).is_ok_and(
+    |modules| modules.lines().any(
+      |line| line.split_whitespace().next() == Some(module)
+    )
+  );
+  is_listed || paths.sys_root.join("module").join(module).exists()
+}
+
+fn _get_sysctl_path(
+  paths: &SystemPaths,
+  parameter: &str
+) -> PathBuf {
+  paths.proc_root.join("sys").join(parameter.replace('.', "/"))
+}
+
+fn _is_sysctl_enabled(
+  paths: &SystemPaths,
+  parameter: &str
+) -> bool {
+  _read_trimmed(&_get_sysctl_path(paths, parameter)).is_ok_and(
+    |value| value == "1"
+  )
+}
+
+fn _get_missing_kernel_modules(
+  paths: &SystemPaths
+) -> Vec<&'static str> {
+  REQUIRED_KERNEL_MODULES
+    .iter()
+    .filter(|module| !_is_kernel_module_loaded(paths, module))
+    .copied()
+    .collect()
+}
+
+fn _get_disabled_sysctls(
+  paths: &SystemPaths
+) -> Vec<&'static str> {
+  REQUIRED_SYSCTLS
+    .iter()
+    .filter(|parameter| !_is_sysctl_enabled(paths, parameter))
+    .copied()
+    .collect()
+}
+
+fn _kernel_module_check(
+  paths: &SystemPaths,
+  module: &str
+) -> Result<(), InstallError> {
+  if _is_kernel_module_loaded(paths, module) {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Kernel module `{}` is not loaded", module)
+    )
+  )
+}
+
+fn _sysctl_check(
+  paths: &SystemPaths,
+  parameter: &str
+) -> Result<(), InstallError> {
+  if _is_sysctl_enabled(paths, parameter) {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Kernel parameter `{}` is not set to 1", parameter)
+    )
+  )
+}
+
+fn _swap_check(
+  paths: &SystemPaths
+) -> Result<(), InstallError> {
+  let swaps = _read_trimmed(&paths.proc_root.join("swaps"))?;
+  // First line is a header
+  let active_swaps = swaps
+    .lines()
+    .skip(1)
+    .filter_map(|line| line.split_whitespace().next())
+    .collect::<Vec<&str>>();
+  if active_swaps.is_empty() {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Swap must be turned off, active swaps: {:?}", active_swaps)
+    )
+  )
+}
+
+pub fn detect_cgroup_version(
+  paths: &SystemPaths
+) -> Result<CgroupVersion, InstallError> {
+  let cgroup_root = paths.sys_root.join("fs").join("cgroup");
+  if cgroup_root.join("cgroup.controllers").exists() {
+    return Ok(CgroupVersion::V2)
+  }
+  if cgroup_root.is_dir() {
+    return Ok(CgroupVersion::V1)
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("No cgroup hierarchy mounted at `{}`", cgroup_root.display())
+    )
+  )
+}
+
+fn _systemd_check(
+  paths: &SystemPaths
+) -> Result<(), InstallError> {
+  let init_name = _read_trimmed(&paths.proc_root.join("1").join("comm"))?;
+  if init_name != "systemd" {
+    return Err(
+      InstallError::new(
+        ErrorKind::Preflight,
+        format!("PID 1 is `{}`, but systemd is required", init_name)
+      )
+    )
+  }
+
+  let has_systemctl = env::var_os("PATH").is_some_and(
+    |path| env::split_paths(&path).any(|dir| dir.join("systemctl").is_file())
+  );
+  if !has_systemctl {
+    return Err(
+      InstallError::new_from_str(
+        ErrorKind::Preflight,
+        "`systemctl` was not found in PATH"
+      )
+    )
+  }
+  Ok(())
+}
+
+fn _parse_kernel_version(
+  release: &str
+) -> Option<(u32, u32)> {
+  let mut parts = release.split(|c: char| !c.is_ascii_digit());
+  let major = parts.next()?.parse::<u32>().ok()?;
+  let minor = parts.next()?.parse::<u32>().ok()?;
+  Some((major, minor))
+}
+
+fn _kernel_version_check(
+  paths: &SystemPaths
+) -> Result<(), InstallError> {
+  let release = _read_trimmed(
+    &paths.proc_root.join("sys").join("kernel").join("osrelease")
+  )?;
+  let version = _parse_kernel_version(&release).ok_or_else(||
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!("Could not parse kernel version `{}`", release)
+    )
+  )?;
+  if version >= MIN_KERNEL_VERSION {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!(
+        "Kernel `{}` is older than required {}.{}",
+        release,
+        MIN_KERNEL_VERSION.0,
+        MIN_KERNEL_VERSION.1
+      )
+    )
+  )
+}
+
+fn _to_c_path(
+  path: &Path
+) -> Result<CString, InstallError> {
+  path.to_str()
+    .and_then(|x| CString::new(x).ok())
+    .ok_or_else(|| InstallError::new(
+      ErrorKind::Other,
+      format!("Could not stringify `{:#?}`", path)
+    ))
+}
+
+// Installation dir does not have to exist yet - in such case
+// its closest existing parent is what matters
+fn _get_closest_existing_dir(
+  path: &Path
+) -> Option<&Path> {
+  path.ancestors().find(|x| x.is_dir())
+}
+
+fn _installation_dir_check(
+  installation_dir: &Path
+) -> Result<(), InstallError> {
+  let existing_dir = _get_closest_existing_dir(installation_dir)
+    .unwrap_or(installation_dir);
+  let c_path = _to_c_path(existing_dir)?;
+  if unsafe { libc::access(c_path.as_ptr(), libc::W_OK) } == 0 {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!(
+        "Installation dir `{}` is not writable ({})",
+        installation_dir.display(),
+        std::io::Error::last_os_error()
+      )
+    )
+  )
+}
+
+fn _get_free_space_mb(
+  path: &Path
+) -> Result<u64, InstallError> {
+  let c_path = _to_c_path(path)?;
+  let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+  let status = unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) };
+  if status != 0 {
+    return Err(std::io::Error::last_os_error().into())
+  }
+  Ok((stats.f_bavail as u64) * (stats.f_frsize as u64) / 1024 / 1024)
+}
+
+fn _free_disk_space_check(
+  installation_dir: &Path
+) -> Result<(), InstallError> {
+  let free_space_mb = _get_free_space_mb(installation_dir)?;
+  if free_space_mb >= MIN_FREE_DISK_SPACE_MB {
+    return Ok(())
+  }
+  Err(
+    InstallError::new(
+      ErrorKind::Preflight,
+      format!(
+        "Only {}MB is free under `{}`, at least {}MB is required",
+        free_space_mb,
+        installation_dir.display(),
+        MIN_FREE_DISK_SPACE_MB
+      )
+    )
+  )
+}
+
+fn _run_fix_command(
+  host: &dyn Host,
+  program: &str,
+  args: &[&str]
+) -> Result<(), InstallError> {
+  host.run_checked(program, args, ErrorKind::Preflight)?;
+  Ok(())
+}
+
+fn _save_drop_in<T: Template>(
+  host: &dyn Host,
+  template: T,
+  path: &str
+) -> Result<(), InstallError> {
+  let path = Path::new(path);
+  if let Some(parent) = path.parent() {
+    host.create_dir_all(parent)?;
+  }
+  render_and_save(host, template, path)
+}
+
+fn _fix_kernel_prerequisites(
+  host: &dyn Host,
+  paths: &SystemPaths
+) -> Result<(), InstallError> {
+  // Kernel of an image is not running yet, so there
+  // is nothing to inspect - drop-ins are always written
+  let is_rooted = host.root().is_some();
+
+  let missing_modules = match is_rooted {
+    true => REQUIRED_KERNEL_MODULES.to_vec(),
+    false => _get_missing_kernel_modules(paths)
+  };
+  if !missing_modules.is_empty() {
+    info!("Loading kernel modules {:?}", missing_modules);
+    _save_drop_in(
+      host,
+      ModulesLoadTemplate { modules: &REQUIRED_KERNEL_MODULES },
+      MODULES_LOAD_DROP_IN_PATH
+    )?;
+    for module in missing_modules.iter().filter(|_| !is_rooted) {
+      _run_fix_command(host, "modprobe", &[module])?;
+    }
+  }
+
+  let disabled_sysctls = match is_rooted {
+    true => REQUIRED_SYSCTLS.to_vec(),
+    false => _get_disabled_sysctls(paths)
+  };
+  if !disabled_sysctls.is_empty() {
+    info!("Enabling kernel parameters {:?}", disabled_sysctls);
+    _save_drop_in(
+      host,
+      SysctlTemplate { parameters: &REQUIRED_SYSCTLS },
+      SYSCTL_DROP_IN_PATH
+    )?;
+    if !is_rooted {
+      _run_fix_command(host, "sysctl", &["--system"])?;
+    }
+  }
+  Ok(())
+}
+
+fn _record_running_system_checks(
+  report: &mut PreflightReport,
+  paths: &SystemPaths
+) {
+  for module in REQUIRED_KERNEL_MODULES.iter() {
+    report.record(
+      &format!("kernel module {} is loaded", module),
+      _kernel_module_check(paths, module)
+    );
+  }
+  for parameter in REQUIRED_SYSCTLS.iter() {
+    report.record(
+      &format!("kernel parameter {} is enabled", parameter),
+      _sysctl_check(paths, parameter)
+    );
+  }
+  report.record("swap is off", _swap_check(paths));
+  report.record(
+    "cgroups are available",
+    detect_cgroup_version(paths).map(|version|
+      info!("Detected cgroup version: {:?}", version)
+    )
+  );
+  report.record("systemd is pid 1", _systemd_check(paths));
+  report.record("kernel version", _kernel_version_check(paths));
+}
+
+pub fn system_preflight(
+  host: &dyn Host,
+  settings: &Settings,
+  paths: &SystemPaths,
+  fix: bool
+) -> Result<(), InstallError> {
+  if fix {
+    _fix_kernel_prerequisites(host, paths)?;
+  }
+
+  let mut report = PreflightReport::new();
+  match host.root() {
+    Some(root) => info!(
+      "Installing under `{}`, checks of the running system are skipped",
+      root.display()
+    ),
+    None => _record_running_system_checks(&mut report, paths)
+  }
+
+  let installation_dir = rooted(
+    host.root(),
+    Path::new(&settings.installation_dir)
+  );
+  report.record(
+    "installation dir is writable",
+    _installation_dir_check(&installation_dir)
+  );
+  if let Some(existing_dir) = _get_closest_existing_dir(&installation_dir) {
+    report.record(
+      "free disk space",
+      _free_disk_space_check(existing_dir)
+    );
+  }
+
+  if !report.is_ok() && !fix {
+    info!("Some of the failures may be fixed by re-running with `--fix`");
+  }
+  report.finish()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs::{create_dir_all, write};
+  use tempfile::TempDir;
+  use super::*;
+
+  fn _create_fixture(
+    root: &TempDir,
+    relative_path: &str,
+    contents: &str
+  ) {
+    let path = root.path().join(relative_path);
+    create_dir_all(path.parent().unwrap()).unwrap();
+    write(path, contents).unwrap();
+  }
+
+  fn _fixture_paths(
+    root: &TempDir
+  ) -> SystemPaths {
+    SystemPaths {
+      proc_root: root.path().join("proc"),
+      sys_root: root.path().join("sys")
+    }
+  }
+
+  #[test]
+  fn test_kernel_modules_and_sysctls() {
+    let root = TempDir::new().unwrap();
+    let paths = _fixture_paths(&root);
+    _create_fixture(&root, "proc/modules", "overlay 118784 0 - Live 0x0\n");
+    _create_fixture(&root, "proc/sys/net/ipv4/ip_forward", "1\n");
+    _create_fixture(&root, "proc/sys/net/bridge/bridge-nf-call-iptables", "0\n");
+
+    assert_eq!(_get_missing_kernel_modules(&paths), vec!["br_netfilter"]);
+    assert_eq!(
+      _get_disabled_sysctls(&paths),
+      vec!["net.bridge.bridge-nf-call-iptables"]
+    );
+
+    // Built-in module
+    create_dir_all(root.path().join("sys/module/br_netfilter")).unwrap();
+    assert!(_get_missing_kernel_modules(&paths).is_empty());
+  }
+
+  #[test]
+  fn test_swap_detection() {
+    let root = TempDir::new().unwrap();
+    let paths = _fixture_paths(&root);
+    let header = "Filename\tType\tSize\tUsed\tPriority\n";
+
+    _create_fixture(&root, "proc/swaps", header);
+    assert!(_swap_check(&paths).is_ok());
+
+    _create_fixture(
+      &root,
+      "proc/swaps",
+      &format!("{}/swapfile\tfile\t2097148\t0\t-2\n", header)
+    );
+    assert!(_swap_check(&paths).is_err());
+  }
+
+  #[test]
+  fn test_cgroup_version_detection() {
+    let root = TempDir::new().unwrap();
+    let paths = _fixture_paths(&root);
+    assert!(detect_cgroup_version(&paths).is_err());
+
+    create_dir_all(root.path().join("sys/fs/cgroup/memory")).unwrap();
+    assert_eq!(detect_cgroup_version(&paths).unwrap(), CgroupVersion::V1);
+
+    _create_fixture(&root, "sys/fs/cgroup/cgroup.controllers", "cpu io memory\n");
+    assert_eq!(detect_cgroup_version(&paths).unwrap(), CgroupVersion::V2);
+  }
+
+  #[test]
+  fn test_kernel_version() {
+    assert_eq!(_parse_kernel_version("5.10.0-18-amd64"), Some((5, 10)));
+    assert_eq!(_parse_kernel_version("4.9"), Some((4, 9)));
+    assert_eq!(_parse_kernel_version("unknown"), None);
+
+    let root = TempDir::new().unwrap();
+    let paths = _fixture_paths(&root);
+    _create_fixture(&root, "proc/sys/kernel/osrelease", "3.10.0-1160.el7.x86_64\n");
+    assert!(_kernel_version_check(&paths).is_err());
+    _create_fixture(&root, "proc/sys/kernel/osrelease", "5.4.0-42-generic\n");
+    assert!(_kernel_version_check(&paths).is_ok());
+  }
+
+  #[test]
+  fn test_systemd_must_be_pid_1() {
+    let root = TempDir::new().unwrap();
+    let paths = _fixture_paths(&root);
+    _create_fixture(&root, "proc/1/comm", "init\n");
+    assert!(_systemd_check(&paths).is_err());
+  }
+
+  #[test]
+  fn test_installation_dir_is_writable() {
+    let root = TempDir::new().unwrap();
+    let installation_dir = root.path().join("rusty-sailor");
+    assert!(_installation_dir_check(&installation_dir).is_ok());
+    assert!(!installation_dir.exists());
+    assert_eq!(
+      _get_closest_existing_dir(&installation_dir),
+      Some(root.path())
+    );
+    assert!(_get_free_space_mb(root.path()).is_ok());
+  }
+}
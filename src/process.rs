@@ -0,0 +1,198 @@
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::errors::{ErrorKind, InstallError};
+
+// Long enough for `etcdctl member add` against a slow peer
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+const STDERR_TAIL_LINES: usize = 10;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// Arguments are shown as given, they are never
+// interpolated into a shell command
+pub fn command_line(
+  program: &str,
+  args: &[&str]
+) -> String {
+  let mut command = vec![program];
+  command.extend_from_slice(args);
+  command.join(" ")
+}
+
+// Pipes are read in the background, so that a program
+// writing a lot does not block on a full pipe
+fn _drain<R: Read + Send + 'static>(
+  pipe: Option<R>
+) -> JoinHandle<Vec<u8>> {
+  thread::spawn(move || {
+    let mut contents = vec![];
+    if let Some(mut pipe) = pipe {
+      let _ = pipe.read_to_end(&mut contents);
+    }
+    contents
+  })
+}
+
+fn _wait(
+  child: &mut Child,
+  timeout: Duration
+) -> Result<Option<ExitStatus>, InstallError> {
+  let started_at = Instant::now();
+  loop {
+    if let Some(status) = child.try_wait()? {
+      return Ok(Some(status))
+    }
+    if started_at.elapsed() >= timeout {
+      child.kill()?;
+      child.wait()?;
+      return Ok(None)
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+}
+
+fn _log_stream(
+  name: &str,
+  contents: &[u8]
+) {
+  if !contents.is_empty() {
+    debug!("{}:\n{}", name, String::from_utf8_lossy(contents).trim_end());
+  }
+}
+
+// Runs program with given arguments, without any shell in between.
+// It is killed, when it does not exit within `timeout`
+pub fn run(
+  program: &str,
+  args: &[&str],
+  timeout: Duration
+) -> Result<Output, InstallError> {
+  let command = command_line(program, args);
+  debug!("Running `{}`", command);
+  let mut child = Command::new(program)
+    .args(args)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|e|
+      InstallError::with_source(
+        ErrorKind::Command,
+        format!("Command `{}` could not be started", command),
+        e
+      )
+    )?;
+  let stdout = _drain(child.stdout.take());
+  let stderr = _drain(child.stderr.take());
+
+  let status = match _wait(&mut child, timeout)? {
+    Some(status) => status,
+    None => return Err(
+      InstallError::new(
+        ErrorKind::Command,
+        format!("Command `{}` has timed out after {:?}", command, timeout)
+      )
+    )
+  };
+  let output = Output {
+    status,
+    stdout: stdout.join().unwrap_or_default(),
+    stderr: stderr.join().unwrap_or_default()
+  };
+  debug!("`{}` has exited with {}", command, status);
+  _log_stream("stdout", &output.stdout);
+  _log_stream("stderr", &output.stderr);
+  Ok(output)
+}
+
+// Last lines of stderr, which usually explain the failure
+pub fn stderr_tail(
+  output: &Output
+) -> String {
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  let lines = stderr.trim_end().lines().collect::<Vec<&str>>();
+  lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+}
+
+// Turns unsuccessful exit into an error of given kind
+pub fn check(
+  program: &str,
+  args: &[&str],
+  output: Output,
+  kind: ErrorKind
+) -> Result<Output, InstallError> {
+  if output.status.success() {
+    return Ok(output)
+  }
+  let tail = stderr_tail(&output);
+  let details = match tail.is_empty() {
+    true => String::new(),
+    false => format!(":\n{}", tail)
+  };
+  Err(
+    InstallError::new(
+      kind,
+      format!(
+        "Command `{}` has failed ({}){}",
+        command_line(program, args),
+        output.status,
+        details
+      )
+    )
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_output_is_captured() {
+    let output = run("echo", &["; rm -rf /", "$HOME"], DEFAULT_TIMEOUT).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "; rm -rf / $HOME\n");
+  }
+
+  #[test]
+  fn test_failure_carries_stderr() {
+    let args = ["/nonexistent/rusty-sailor"];
+    let output = run("ls", &args, DEFAULT_TIMEOUT).unwrap();
+    let error = check("ls", &args, output, ErrorKind::Etcd).err().unwrap();
+    assert!(matches!(error.kind, ErrorKind::Etcd));
+    assert!(error.msg.starts_with("Command `ls /nonexistent/rusty-sailor` has failed"));
+    assert!(error.msg.contains("No such file or directory"));
+  }
+
+  #[test]
+  fn test_command_is_killed_after_timeout() {
+    let started_at = Instant::now();
+    let error = run("sleep", &["5"], Duration::from_millis(100)).err().unwrap();
+    assert!(matches!(error.kind, ErrorKind::Command));
+    assert!(started_at.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn test_missing_program_is_reported() {
+    let error = run("/nonexistent/rusty-sailor", &[], DEFAULT_TIMEOUT).err().unwrap();
+    assert!(error.msg.contains("could not be started"));
+    assert!(error.report().contains("caused by: No such file or directory"));
+  }
+
+  #[test]
+  fn test_stderr_tail() {
+    let stderr = (1..=15).map(|x| format!("line {}\n", x)).collect::<String>();
+    let output = Output {
+      status: run("true", &[], DEFAULT_TIMEOUT).unwrap().status,
+      stdout: vec![],
+      stderr: stderr.into_bytes()
+    };
+    let tail = stderr_tail(&output);
+    assert!(tail.starts_with("line 6\n"));
+    assert!(tail.ends_with("line 15"));
+  }
+}
@@ -0,0 +1,270 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::report::ComponentStatus;
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Emitted by the runner and by long operations,
+// which would otherwise show nothing for a while
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  RunStarted { components: Vec<String> },
+  ComponentStarted { name: String },
+  ComponentFinished { name: String, status: ComponentStatus, duration: Duration },
+  TaskStarted { description: String },
+  TaskFinished,
+  RunFinished
+}
+
+#[derive(Debug)]
+enum ComponentState {
+  Pending,
+  Running(Instant),
+  Finished(ComponentStatus, Duration)
+}
+
+// Component list, redrawn in place below everything printed so far
+struct View {
+  components: Vec<(String, ComponentState)>,
+  task: Option<String>,
+  frame: usize,
+  drawn_lines: usize
+}
+
+// Without a view (stdout is not a TTY, or it was not enabled)
+// events are dropped and log lines are all there is
+static VIEW: Mutex<Option<View>> = Mutex::new(None);
+
+fn _format_duration(
+  duration: Duration
+) -> String {
+  format!("{:.1}s", duration.as_secs_f64())
+}
+
+impl View {
+  fn handle(
+    &mut self,
+    event: &Event
+  ) {
+    match event {
+      Event::RunStarted { components } => {
+        self.components = components
+          .iter()
+          .map(|name| (name.clone(), ComponentState::Pending))
+          .collect();
+      },
+      Event::ComponentStarted { name } => self.set_state(name, ComponentState::Running(Instant::now())),
+      Event::ComponentFinished { name, status, duration } => {
+        self.task = None;
+        self.set_state(name, ComponentState::Finished(*status, *duration));
+      },
+      Event::TaskStarted { description } => self.task = Some(description.clone()),
+      Event::TaskFinished => self.task = None,
+      Event::RunFinished => {}
+    }
+  }
+
+  fn set_state(
+    &mut self,
+    name: &str,
+    state: ComponentState
+  ) {
+    match self.components.iter_mut().find(|(x, _)| x == name) {
+      Some((_, current)) => *current = state,
+      None => self.components.push((name.to_string(), state))
+    }
+  }
+
+  fn lines(&self) -> Vec<String> {
+    let width = self.components.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    self.components.iter().map(|(name, state)| match state {
+      ComponentState::Pending => format!("  \x1b[2m·\x1b[0m {}", name),
+      ComponentState::Running(started_at) => format!(
+        "  \x1b[36m{}\x1b[0m {:width$}  {}{}",
+        SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()],
+        name,
+        _format_duration(started_at.elapsed()),
+        self.task.as_ref().map_or_else(String::new, |x| format!("  {}", x)),
+        width = width
+      ),
+      ComponentState::Finished(ComponentStatus::Completed, duration) => format!(
+        "  \x1b[32m✔\x1b[0m {:width$}  {}", name, _format_duration(*duration), width = width
+      ),
+      ComponentState::Finished(ComponentStatus::Failed, duration) => format!(
+        "  \x1b[31m✘\x1b[0m {:width$}  {}", name, _format_duration(*duration), width = width
+      ),
+      ComponentState::Finished(ComponentStatus::Skipped, _) => format!(
+        "  \x1b[2m↷\x1b[0m {:width$}  already completed", name, width = width
+      )
+    }).collect()
+  }
+
+  fn clear(
+    &mut self,
+    out: &mut dyn Write
+  ) {
+    if self.drawn_lines > 0 {
+      let _ = write!(out, "\x1b[{}A\r\x1b[J", self.drawn_lines);
+    }
+    self.drawn_lines = 0;
+  }
+
+  fn draw(
+    &mut self,
+    out: &mut dyn Write
+  ) {
+    self.clear(out);
+    let lines = self.lines();
+    for line in lines.iter() {
+      let _ = writeln!(out, "{}", line);
+    }
+    self.drawn_lines = lines.len();
+    let _ = out.flush();
+  }
+}
+
+// Spinners are animated in the background,
+// until the run finishes
+fn _tick() {
+  loop {
+    thread::sleep(TICK_INTERVAL);
+    let mut view = match VIEW.lock() {
+      Ok(view) => view,
+      Err(_) => return
+    };
+    match view.as_mut() {
+      Some(view) => {
+        view.frame += 1;
+        view.draw(&mut io::stdout());
+      },
+      None => return
+    }
+  }
+}
+
+pub fn is_terminal() -> bool {
+  unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+pub fn enable_view() {
+  if let Ok(mut view) = VIEW.lock() {
+    *view = Some(
+      View {
+        components: vec![],
+        task: None,
+        frame: 0,
+        drawn_lines: 0
+      }
+    );
+  }
+  thread::spawn(_tick);
+}
+
+pub fn is_view_enabled() -> bool {
+  VIEW.lock().is_ok_and(|view| view.is_some())
+}
+
+pub fn emit(
+  event: Event
+) {
+  let mut guard = match VIEW.lock() {
+    Ok(guard) => guard,
+    Err(_) => return
+  };
+  let view = match guard.as_mut() {
+    Some(view) => view,
+    None => return
+  };
+  view.handle(&event);
+  view.draw(&mut io::stdout());
+  // Final state stays on the screen
+  if event == Event::RunFinished {
+    *guard = None;
+  }
+}
+
+// Prints line above the view, so that it is not overwritten.
+// Returns false when there is no view
+pub fn print_above(
+  line: &str
+) -> bool {
+  let mut guard = match VIEW.lock() {
+    Ok(guard) => guard,
+    Err(_) => return false
+  };
+  let view = match guard.as_mut() {
+    Some(view) => view,
+    None => return false
+  };
+  let mut out = io::stdout();
+  view.clear(&mut out);
+  let _ = writeln!(out, "{}", line);
+  view.draw(&mut out);
+  true
+}
+
+// Long operation within a component, e.g. RSA key generation.
+// It is logged as well, for terminals without the view
+pub fn task<T, F: FnOnce() -> T>(
+  description: &str,
+  operation: F
+) -> T {
+  info!("{}", description);
+  emit(Event::TaskStarted { description: description.to_string() });
+  let result = operation();
+  emit(Event::TaskFinished);
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn _view() -> View {
+    View { components: vec![], task: None, frame: 0, drawn_lines: 0 }
+  }
+
+  #[test]
+  fn test_components_are_listed_with_state() {
+    let mut view = _view();
+    view.handle(&Event::RunStarted { components: vec!["ca".to_string(), "etcd".to_string()] });
+    view.handle(&Event::ComponentFinished {
+      name: "ca".to_string(),
+      status: ComponentStatus::Completed,
+      duration: Duration::from_millis(5300)
+    });
+    view.handle(&Event::ComponentStarted { name: "etcd".to_string() });
+    view.handle(&Event::TaskStarted { description: "Unpacking etcd.tar.gz".to_string() });
+
+    let lines = view.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("✔") && lines[0].contains("ca  ") && lines[0].ends_with("5.3s"));
+    assert!(lines[1].contains("etcd") && lines[1].ends_with("Unpacking etcd.tar.gz"));
+
+    view.handle(&Event::ComponentFinished {
+      name: "etcd".to_string(),
+      status: ComponentStatus::Failed,
+      duration: Duration::from_millis(100)
+    });
+    assert!(view.lines()[1].contains("✘"));
+    assert!(!view.lines()[1].contains("Unpacking"));
+  }
+
+  #[test]
+  fn test_view_is_redrawn_in_place() {
+    let mut view = _view();
+    view.handle(&Event::RunStarted { components: vec!["ca".to_string(), "etcd".to_string()] });
+    let mut out = vec![];
+    view.draw(&mut out);
+    view.draw(&mut out);
+    let drawn = String::from_utf8(out).unwrap();
+    assert_eq!(drawn.matches("\x1b[2A").count(), 1);
+    assert_eq!(view.drawn_lines, 2);
+  }
+}
@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::x509::X509;
+use serde::Serialize;
+
+use crate::errors::InstallError;
+use crate::fs::{write_atomic, FileAttributes};
+use crate::host::Host;
+use crate::host::tracking::Artifact;
+use crate::pki::cert::common_name;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+  Completed,
+  Failed,
+  // Completed by one of previous runs
+  Skipped
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentReport {
+  pub name: String,
+  pub status: ComponentStatus,
+  pub duration_secs: f64,
+  // Whatever is left on the node, changes
+  // of failed component are usually rolled back
+  pub artifacts: Vec<Artifact>
+}
+
+#[derive(Debug, Serialize)]
+pub struct CertificateReport {
+  pub path: PathBuf,
+  pub subject: String,
+  pub issuer: String,
+  // Hex encoded
+  pub serial: String,
+  // Colon separated, as printed by `openssl x509 -fingerprint -sha256`
+  pub sha256_fingerprint: String,
+  pub not_after: String
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Endpoint {
+  pub name: String,
+  pub url: String
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+  pub kind: String,
+  pub exit_code: i32,
+  pub message: String,
+  pub causes: Vec<String>
+}
+
+// Outcome of installation, written with `--report`
+// for provisioning systems, instead of scraping logs
+#[derive(Debug, Serialize)]
+pub struct InstallReport {
+  pub version: String,
+  pub succeeded: bool,
+  pub started_at: String,
+  pub duration_secs: f64,
+  pub components: Vec<ComponentReport>,
+  pub certificates: Vec<CertificateReport>,
+  pub endpoints: Vec<Endpoint>,
+  pub error: Option<ErrorReport>,
+  #[serde(skip)]
+  started: Instant
+}
+
+fn _rfc3339(
+  time: &Asn1TimeRef
+) -> Result<String, ErrorStack> {
+  let since_epoch = Asn1Time::from_unix(0)?.diff(time)?;
+  let seconds = i64::from(since_epoch.days) * 86400 + i64::from(since_epoch.secs);
+  Ok(
+    humantime::format_rfc3339_seconds(
+      UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64)
+    ).to_string()
+  )
+}
+
+fn _fingerprint(
+  certificate: &X509
+) -> Result<String, ErrorStack> {
+  Ok(
+    certificate.digest(MessageDigest::sha256())?
+      .iter()
+      .map(|x| format!("{:02X}", x))
+      .collect::<Vec<String>>()
+      .join(":")
+  )
+}
+
+impl CertificateReport {
+  pub fn new(
+    path: &Path,
+    certificate: &X509
+  ) -> Result<Self, InstallError> {
+    Ok(
+      CertificateReport {
+        path: path.to_path_buf(),
+        subject: common_name(certificate.subject_name()),
+        issuer: common_name(certificate.issuer_name()),
+        serial: certificate.serial_number().to_bn()?.to_hex_str()?.to_string(),
+        sha256_fingerprint: _fingerprint(certificate)?,
+        not_after: _rfc3339(certificate.not_after())?
+      }
+    )
+  }
+}
+
+// Certificates among created files, private keys
+// share the extension and are skipped
+fn _find_certificates(
+  host: &dyn Host,
+  artifacts: &[Artifact]
+) -> Vec<CertificateReport> {
+  artifacts
+    .iter()
+    .filter_map(|artifact| match artifact {
+      Artifact::File { path } | Artifact::ReplacedFile { path, .. } => Some(path),
+      _ => None
+    })
+    .filter(|path| path.extension().is_some_and(|x| x == "pem"))
+    .filter_map(|path| {
+      let contents = host.read_file(path).ok()??;
+      let certificate = X509::from_pem(&contents).ok()?;
+      CertificateReport::new(path, &certificate).ok()
+    })
+    .collect()
+}
+
+impl InstallReport {
+  pub fn new() -> Self {
+    InstallReport {
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      succeeded: false,
+      started_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+      duration_secs: 0.0,
+      components: vec![],
+      certificates: vec![],
+      endpoints: vec![],
+      error: None,
+      started: Instant::now()
+    }
+  }
+
+  pub fn add_component(
+    &mut self,
+    host: &dyn Host,
+    name: &str,
+    status: ComponentStatus,
+    duration: Duration,
+    artifacts: Vec<Artifact>
+  ) {
+    self.certificates.extend(_find_certificates(host, &artifacts));
+    self.components.push(
+      ComponentReport {
+        name: name.to_string(),
+        status,
+        duration_secs: duration.as_secs_f64(),
+        artifacts
+      }
+    );
+  }
+
+  pub fn add_endpoints(
+    &mut self,
+    endpoints: Vec<Endpoint>
+  ) {
+    self.endpoints.extend(endpoints);
+  }
+
+  pub fn finish(
+    &mut self,
+    result: &Result<(), InstallError>
+  ) {
+    self.duration_secs = self.started.elapsed().as_secs_f64();
+    self.succeeded = result.is_ok();
+    self.error = result.as_ref().err().map(|error| ErrorReport {
+      kind: format!("{:?}", error.kind),
+      exit_code: error.exit_code(),
+      message: error.to_string(),
+      causes: error.causes()
+    });
+  }
+
+  // Report describes the node, but is written
+  // locally - even for dry run or custom root
+  pub fn save(
+    &self,
+    path: &Path
+  ) -> Result<(), InstallError> {
+    let contents = format!("{}\n", serde_json::to_string_pretty(self)?);
+    let attributes = FileAttributes::PUBLIC;
+    Ok(write_atomic(path, contents.as_bytes(), attributes.mode, attributes.owner)?)
+  }
+}
+
+impl Default for InstallReport {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config;
+  use crate::host::fake::FakeHost;
+  use crate::pki::cert::create_ca_certificate;
+  use super::*;
+
+  #[test]
+  fn test_certificates_are_described() {
+    let host = FakeHost::new();
+    let (private_key, certificate) = create_ca_certificate(
+      &config::PkiSettings { rsa_size: 1024, ..Default::default() }
+    ).unwrap();
+    host.create_dir_all(Path::new("/opt/pki")).unwrap();
+    host.write_file(Path::new("/opt/pki/ca.pem"), &certificate.to_pem().unwrap(), FileAttributes::PUBLIC).unwrap();
+    host.write_file(
+      Path::new("/opt/pki/ca.private-key.pem"),
+      &private_key.private_key_to_pem_pkcs8().unwrap(),
+      FileAttributes::PRIVATE
+    ).unwrap();
+
+    let mut report = InstallReport::new();
+    report.add_component(
+      &host,
+      "ca",
+      ComponentStatus::Completed,
+      Duration::from_millis(1500),
+      vec![
+        Artifact::Directory { path: PathBuf::from("/opt/pki") },
+        Artifact::File { path: PathBuf::from("/opt/pki/ca.private-key.pem") },
+        Artifact::File { path: PathBuf::from("/opt/pki/ca.pem") }
+      ]
+    );
+    assert_eq!(report.certificates.len(), 1);
+    let described = &report.certificates[0];
+    assert_eq!(described.subject, "rusty-sailor-ca");
+    assert_eq!(
+      described.serial,
+      certificate.serial_number().to_bn().unwrap().to_hex_str().unwrap().to_string()
+    );
+    assert_eq!(described.sha256_fingerprint.len(), 32 * 3 - 1);
+    assert!(described.not_after.ends_with('Z'));
+    assert_eq!(report.components[0].duration_secs, 1.5);
+  }
+
+  #[test]
+  fn test_error_chain_is_reported() {
+    let mut report = InstallReport::new();
+    let error = InstallError::with_source(
+      crate::errors::ErrorKind::Preflight,
+      "Preflight checks have failed".to_string(),
+      std::io::Error::new(std::io::ErrorKind::NotFound, "br_netfilter is not loaded")
+    );
+    report.finish(&Err(error));
+
+    let saved = serde_json::to_value(&report).unwrap();
+    assert_eq!(saved["succeeded"], false);
+    assert_eq!(saved["error"]["kind"], "Preflight");
+    assert_eq!(saved["error"]["exit_code"], 20);
+    assert_eq!(saved["error"]["causes"][0], "br_netfilter is not loaded");
+    assert!(saved.get("started").is_none());
+  }
+}
@@ -1,17 +1,18 @@
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
 
 use askama::Template;
 
-use crate::errors::InstallError;
+use crate::errors::{Context, InstallError};
+use crate::fs::FileAttributes;
+use crate::host::Host;
 
 pub fn render_and_save<T: Template>(
+  host: &dyn Host,
   template: T,
   destination_path: &Path
 ) -> Result<(), InstallError> {
-  let rendered_template = template.render()?;
-  let mut file = File::create(destination_path)?;
-  file.write_all(rendered_template.as_bytes())?;
-  Ok(())
+  let rendered_template = template.render()
+    .with_context(|| format!("while rendering {}", destination_path.display()))?;
+  host.write_file(destination_path, rendered_template.as_bytes(), FileAttributes::PUBLIC)
+    .with_context(|| format!("while writing {}", destination_path.display()))
 }
@@ -1,48 +1,712 @@
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::convert::From;
-use std::io::Cursor;
-use std::path::Path;
+use std::ffi::OsString;
+use std::fs::{read_to_string, File};
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path};
 
 use flate2::read::GzDecoder;
+use openssl::sha::Sha256;
 use rust_embed::RustEmbed;
-use tar::Archive;
+use serde::Deserialize;
+use tar::{Archive, EntryType};
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
 
-use crate::errors::{ErrorKind, InstallError};
+use crate::config::ArtifactSource;
+use crate::errors::{Context, ErrorKind, InstallError};
+use crate::fs::replace_atomic;
+use crate::progress;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VendoredArchive {
+  pub name: &'static str,
+  pub version: &'static str,
+  // Hex encoded
+  pub sha256: &'static str
+}
+
+// Generated by build.rs from vendored/manifest.toml
+include!(concat!(env!("OUT_DIR"), "/vendored_manifest.rs"));
+
+// Directories and bundles carry their own manifest,
+// in the same format as vendored/manifest.toml
+const MANIFEST_NAME: &str = "manifest.toml";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 #[derive(RustEmbed)]
 #[folder = "vendored"]
 struct Archives;
 
-pub fn unpack_archive<P: AsRef<Path>>(
-  name: &str,
-  destination: &P
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+  pub name: String,
+  pub version: String,
+  // Hex encoded
+  pub sha256: String
+}
+
+impl ManifestEntry {
+  // Name, that components ask for - "etcd" for "etcd.tar.gz"
+  pub fn artifact_name(&self) -> &str {
+    self.name.split('.').next().unwrap_or(&self.name)
+  }
+}
+
+impl From<&VendoredArchive> for ManifestEntry {
+  fn from(archive: &VendoredArchive) -> Self {
+    ManifestEntry {
+      name: archive.name.to_string(),
+      version: archive.version.to_string(),
+      sha256: archive.sha256.to_string()
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+  #[serde(default)]
+  archive: Vec<ManifestEntry>
+}
+
+// Archive, that component asks for - in any
+// version the source carries, if not given
+#[derive(Clone, Copy, Debug)]
+pub struct ArtifactRequest<'a> {
+  pub source: &'a ArtifactSource,
+  pub name: &'a str,
+  pub version: Option<&'a str>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Compression {
+  Gzip,
+  Xz,
+  Zstd
+}
+
+impl Compression {
+  fn from_name(
+    name: &str
+  ) -> Result<Self, InstallError> {
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+      Ok(Compression::Gzip)
+    }
+    else if name.ends_with(".tar.xz") {
+      Ok(Compression::Xz)
+    }
+    else if name.ends_with(".tar.zst") {
+      Ok(Compression::Zstd)
+    }
+    else {
+      Err(_error(format!("Archive '{}' is neither .tar.gz, .tar.xz nor .tar.zst", name)))
+    }
+  }
+
+  // Decompresses on the fly, so that whole
+  // archive never has to be held in memory
+  fn decoder<'a, R: Read + 'a>(
+    self,
+    reader: R
+  ) -> Result<Box<dyn Read + 'a>, InstallError> {
+    Ok(match self {
+      Compression::Gzip => Box::new(GzDecoder::new(reader)),
+      Compression::Xz => Box::new(XzDecoder::new(reader)),
+      Compression::Zstd => Box::new(ZstdDecoder::new(reader)?)
+    })
+  }
+}
+
+// Compressed archive, that has been verified. Files are copied
+// while being verified, so that the unpacked bytes are the verified
+// ones, even if the original changes in the meantime
+enum Payload {
+  // Borrowed from the binary, or owned when
+  // rust-embed reads it from disk in debug builds
+  Embedded(Cow<'static, [u8]>),
+  // Anonymous temporary file
+  Copy(File)
+}
+
+impl Payload {
+  fn with_reader<T, F: FnOnce(&mut dyn Read) -> Result<T, InstallError>>(
+    &self,
+    operation: F
+  ) -> Result<T, InstallError> {
+    match self {
+      Payload::Embedded(bytes) => operation(&mut Cursor::new(bytes.as_ref())),
+      Payload::Copy(file) => {
+        let mut file = file;
+        file.seek(SeekFrom::Start(0))?;
+        operation(&mut BufReader::new(file))
+      }
+    }
+  }
+}
+
+fn _error(
+  msg: String
+) -> InstallError {
+  InstallError::new(ErrorKind::UnpackArchive, msg)
+}
+
+// Whatever is hashed is written as well
+fn _hex_sha256(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write
+) -> Result<String, InstallError> {
+  let mut hasher = Sha256::new();
+  let mut buffer = [0u8; 64 * 1024];
+  loop {
+    match reader.read(&mut buffer)? {
+      0 => break,
+      read => {
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+      }
+    }
+  }
+  Ok(hasher.finish().iter().map(|x| format!("{:02x}", x)).collect())
+}
+
+// Archive has to match the digest pinned in manifest
+fn _verify(
+  entry: &ManifestEntry,
+  reader: &mut dyn Read,
+  writer: &mut dyn Write
 ) -> Result<(), InstallError> {
-  let package_bytes = Archives::get(name).ok_or_else(||
-    InstallError::new(
-      ErrorKind::UnpackArchive,
-      format!("Archive with name '{}' was not found", name)
+  let digest = _hex_sha256(reader, writer)?;
+  if digest != entry.sha256.to_lowercase() {
+    return Err(
+      _error(
+        format!(
+          "Archive '{}' ({}) does not match its manifest (expected sha256 {}, got {})",
+          entry.name,
+          entry.version,
+          entry.sha256,
+          digest
+        )
+      )
+    )
+  }
+  Ok(())
+}
+
+// Manifest comes along with the archives, so it
+// has to match the digest pinned in configuration
+fn _parse_manifest(
+  origin: &Path,
+  contents: &str,
+  sha256: &str
+) -> Result<Vec<ManifestEntry>, InstallError> {
+  let digest = _hex_sha256(&mut contents.as_bytes(), &mut io::sink())?;
+  if digest != sha256.to_lowercase() {
+    return Err(
+      _error(
+        format!(
+          "Manifest of '{}' does not match artifacts.manifest_sha256 (expected {}, got {})",
+          origin.display(),
+          sha256,
+          digest
+        )
+      )
     )
+  }
+  let manifest = toml::from_str::<Manifest>(contents).map_err(|e|
+    _error(format!("Manifest of '{}' could not be parsed ({})", origin.display(), e))
   )?;
-  match package_bytes {
-    Cow::Borrowed(bytes) => {
-      let tar = GzDecoder::new(
-        Cursor::new(bytes)
-      );
-      let mut archive = Archive::new(
-        tar
-      );
-      let result = archive.unpack(
-        destination
+  // Names are joined to the directory, so they
+  // can not point anywhere outside of it
+  for entry in &manifest.archive {
+    let is_plain_name = Path::new(&entry.name).file_name()
+      .is_some_and(|x| x == entry.name.as_str());
+    if !is_plain_name {
+      return Err(
+        _error(format!("Manifest of '{}' lists invalid name '{}'", origin.display(), entry.name))
+      )
+    }
+  }
+  Ok(manifest.archive)
+}
+
+// Bundle is a tar (optionally gzipped) file,
+// with manifest and archives at its top level
+fn _with_bundle_entry<T, F: FnOnce(&mut dyn Read) -> Result<T, InstallError>>(
+  bundle: &Path,
+  name: &str,
+  operation: F
+) -> Result<T, InstallError> {
+  let mut file = File::open(bundle)?;
+  let mut magic = [0u8; 2];
+  let is_gzipped = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+  file.seek(SeekFrom::Start(0))?;
+  let reader: Box<dyn Read> = match is_gzipped {
+    true => Box::new(GzDecoder::new(BufReader::new(file))),
+    false => Box::new(BufReader::new(file))
+  };
+
+  let mut archive = Archive::new(reader);
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let is_wanted = entry.header().entry_type() == EntryType::Regular
+      && entry.path()?.components().eq(Path::new(name).components());
+    if is_wanted {
+      return operation(&mut entry)
+    }
+  }
+  Err(_error(format!("Bundle '{}' does not contain '{}'", bundle.display(), name)))
+}
+
+pub fn manifest(
+  source: &ArtifactSource
+) -> Result<Vec<ManifestEntry>, InstallError> {
+  match source {
+    ArtifactSource::Embedded => Ok(MANIFEST.iter().map(ManifestEntry::from).collect()),
+    ArtifactSource::Directory { path, manifest_sha256 } => {
+      let path = Path::new(path);
+      let contents = read_to_string(path.join(MANIFEST_NAME))
+        .with_context(|| format!("Directory '{}' has no readable {}", path.display(), MANIFEST_NAME))?;
+      _parse_manifest(path, &contents, manifest_sha256)
+    },
+    ArtifactSource::Bundle { path, manifest_sha256 } => {
+      let path = Path::new(path);
+      let contents = _with_bundle_entry(path, MANIFEST_NAME, |reader| {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        Ok(contents)
+      })?;
+      _parse_manifest(path, &contents, manifest_sha256)
+    }
+  }
+}
+
+fn _resolve(
+  manifest: Vec<ManifestEntry>,
+  name: &str,
+  version: Option<&str>
+) -> Result<ManifestEntry, InstallError> {
+  let mut candidates = manifest
+    .into_iter()
+    .filter(|x| x.artifact_name() == name)
+    .collect::<Vec<ManifestEntry>>();
+  let available = candidates.iter()
+    .map(|x| x.version.clone())
+    .collect::<Vec<String>>()
+    .join(", ");
+  if let Some(version) = version {
+    candidates.retain(|x| x.version == version);
+  }
+  match candidates.len() {
+    1 => Ok(candidates.remove(0)),
+    0 if available.is_empty() => Err(
+      _error(format!("Archive '{}' is not listed in the manifest", name))
+    ),
+    0 => Err(
+      _error(format!(
+        "Archive '{}' is not available in version {} (available: {})",
+        name,
+        version.unwrap_or_default(),
+        available
+      ))
+    ),
+    _ => Err(
+      _error(format!(
+        "Archive '{}' is listed in more than one version ({}), pick one explicitly",
+        name,
+        available
+      ))
+    )
+  }
+}
+
+pub fn is_embedded(
+  name: &str
+) -> bool {
+  Archives::get(name).is_some()
+}
+
+fn _verified_copy(
+  entry: &ManifestEntry,
+  reader: &mut dyn Read
+) -> Result<Payload, InstallError> {
+  let mut copy = tempfile::tempfile()?;
+  _verify(entry, reader, &mut copy)?;
+  Ok(Payload::Copy(copy))
+}
+
+// Payload of requested archive, already verified
+// against the manifest of its source
+fn _payload(
+  request: &ArtifactRequest
+) -> Result<(ManifestEntry, Payload), InstallError> {
+  let entry = _resolve(manifest(request.source)?, request.name, request.version)?;
+  let payload = match request.source {
+    ArtifactSource::Embedded => {
+      let bytes = Archives::get(&entry.name).ok_or_else(||
+        _error(format!("Archive with name '{}' was not found", entry.name))
       )?;
-      Ok(result)
+      _verify(&entry, &mut bytes.as_ref(), &mut io::sink())?;
+      Payload::Embedded(bytes)
+    },
+    ArtifactSource::Directory { path, .. } => _verified_copy(
+      &entry,
+      &mut BufReader::new(File::open(Path::new(path).join(&entry.name))?)
+    )?,
+    ArtifactSource::Bundle { path, .. } => _with_bundle_entry(
+      Path::new(path),
+      &entry.name,
+      |reader| _verified_copy(&entry, reader)
+    )?
+  };
+  Ok((entry, payload))
+}
+
+pub fn unpack_archive<P: AsRef<Path>>(
+  request: &ArtifactRequest,
+  destination: &P
+) -> Result<(), InstallError> {
+  let (entry, payload) = _payload(request)?;
+  let compression = Compression::from_name(&entry.name)?;
+  payload.with_reader(|reader| {
+    let mut archive = Archive::new(
+      compression.decoder(reader)?
+    );
+    progress::task(
+      &format!("Unpacking {} ({})", entry.name, entry.version),
+      || archive.unpack(destination)
+    )?;
+    Ok(())
+  })
+}
+
+// Streams archive entries and writes only files with given names
+// directly into destination (without their parent directories)
+pub fn extract_files<P: AsRef<Path>>(
+  request: &ArtifactRequest,
+  destination: &P,
+  file_names: &HashSet<OsString>
+) -> Result<(), InstallError> {
+  let (entry, payload) = _payload(request)?;
+  let compression = Compression::from_name(&entry.name)?;
+  payload.with_reader(|reader| {
+    let mut archive = Archive::new(
+      compression.decoder(reader)?
+    );
+    progress::task(
+      &format!("Extracting {} from {} ({})", _joined(file_names), entry.name, entry.version),
+      || _extract_files(&entry.name, &mut archive, destination.as_ref(), file_names)
+    )
+  })
+}
+
+fn _joined(
+  file_names: &HashSet<OsString>
+) -> String {
+  let mut names = file_names.iter()
+    .map(|x| x.to_string_lossy().to_string())
+    .collect::<Vec<String>>();
+  names.sort();
+  names.join(", ")
+}
+
+fn _extract_files<R: Read>(
+  name: &str,
+  archive: &mut Archive<R>,
+  destination: &Path,
+  file_names: &HashSet<OsString>
+) -> Result<(), InstallError> {
+  let error = |msg: String| InstallError::new(ErrorKind::UnpackArchive, msg);
+  let mut extracted = HashSet::new();
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let path = entry.path()?.to_path_buf();
+    let is_unsafe = path.components().any(|x| !matches!(x, Component::Normal(_) | Component::CurDir));
+    if is_unsafe {
+      return Err(
+        error(format!("Archive '{}' contains unsafe path '{}'", name, path.display()))
+      )
     }
-    Cow::Owned(_) => {
-      Err(
-        InstallError::new(
-          ErrorKind::UnpackArchive,
-          format!("Could not properly read contents of archive '{}'", name)
+
+    let file_name = match path.file_name() {
+      Some(file_name) if file_names.contains(file_name) => file_name.to_os_string(),
+      _ => continue
+    };
+    match entry.header().entry_type() {
+      EntryType::Regular | EntryType::Continuous => {},
+      EntryType::Symlink | EntryType::Link => {
+        return Err(
+          error(format!("Archive '{}' entry '{}' is a link", name, path.display()))
         )
+      },
+      _ => continue
+    }
+    if !extracted.insert(file_name.clone()) {
+      return Err(
+        error(format!("Archive '{}' contains '{}' more than once", name, file_name.to_string_lossy()))
       )
     }
+
+    // Running binaries cannot be overwritten in place, but they
+    // can be replaced. Only permission bits are taken from the archive
+    let mode = entry.header().mode()? & 0o777;
+    replace_atomic(&destination.join(&file_name), &mut entry, mode, None)?;
+  }
+
+  let missing = file_names.iter()
+    .filter(|x| !extracted.contains(*x))
+    .cloned()
+    .collect::<HashSet<OsString>>();
+  if !missing.is_empty() {
+    return Err(
+      error(format!("Archive '{}' does not contain {}", name, _joined(&missing)))
+    )
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::fs::PermissionsExt;
+  use tar::{Builder, Header};
+  use tempfile::TempDir;
+  use super::*;
+
+  // sha256 of b"etcd"
+  const ETCD_SHA256: &str = "c38cbd92fce41312f3fbdce60510013b7f0d4004a03b45c8e762f98f1ed4006c";
+
+  fn _entry(
+    name: &str,
+    version: &str
+  ) -> ManifestEntry {
+    ManifestEntry {
+      name: name.to_string(),
+      version: version.to_string(),
+      sha256: ETCD_SHA256.to_string()
+    }
+  }
+
+  fn _manifest_toml(
+    version: &str
+  ) -> String {
+    format!(
+      "[[archive]]\nname = \"etcd.tar.gz\"\nversion = \"{}\"\nsha256 = \"{}\"\n",
+      version,
+      ETCD_SHA256
+    )
+  }
+
+  fn _sha256(
+    contents: &str
+  ) -> String {
+    _hex_sha256(&mut contents.as_bytes(), &mut io::sink()).unwrap()
+  }
+
+  fn _contents(
+    payload: &Payload
+  ) -> Vec<u8> {
+    payload.with_reader(|reader| {
+      let mut contents = vec![];
+      reader.read_to_end(&mut contents)?;
+      Ok(contents)
+    }).unwrap()
+  }
+
+  #[test]
+  fn test_archives_are_verified() {
+    let mut copy = vec![];
+    assert!(_verify(&_entry("etcd.tar.gz", "3.4.14"), &mut &b"etcd"[..], &mut copy).is_ok());
+    assert_eq!(copy, b"etcd");
+
+    let error = _verify(
+      &_entry("etcd.tar.gz", "3.4.14"),
+      &mut &b"tampered"[..],
+      &mut io::sink()
+    ).unwrap_err();
+    assert!(error.msg.contains("does not match its manifest"));
+  }
+
+  #[test]
+  fn test_archives_are_resolved_by_name_and_version() {
+    let manifest = vec![
+      _entry("etcd.tar.gz", "3.4.14"),
+      _entry("etcd.tar.gz", "3.4.15"),
+      _entry("containerd.tar.gz", "1.4.3")
+    ];
+    assert_eq!(
+      _resolve(manifest.clone(), "containerd", None).unwrap(),
+      _entry("containerd.tar.gz", "1.4.3")
+    );
+    assert_eq!(
+      _resolve(manifest.clone(), "etcd", Some("3.4.15")).unwrap(),
+      _entry("etcd.tar.gz", "3.4.15")
+    );
+
+    let error = _resolve(manifest.clone(), "etcd", None).unwrap_err();
+    assert!(error.msg.contains("more than one version (3.4.14, 3.4.15)"));
+    let error = _resolve(manifest.clone(), "etcd", Some("3.5.0")).unwrap_err();
+    assert!(error.msg.contains("not available in version 3.5.0"));
+    let error = _resolve(manifest, "kubelet", None).unwrap_err();
+    assert!(error.msg.contains("is not listed"));
+  }
+
+  #[test]
+  fn test_archives_are_read_from_directory() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join(MANIFEST_NAME), _manifest_toml("3.4.15")).unwrap();
+    std::fs::write(dir.path().join("etcd.tar.gz"), b"etcd").unwrap();
+    let source = ArtifactSource::Directory {
+      path: dir.path().display().to_string(),
+      manifest_sha256: _sha256(&_manifest_toml("3.4.15"))
+    };
+
+    let request = ArtifactRequest { source: &source, name: "etcd", version: Some("3.4.15") };
+    let (entry, payload) = _payload(&request).unwrap();
+    assert_eq!(entry, _entry("etcd.tar.gz", "3.4.15"));
+    assert_eq!(_contents(&payload), b"etcd");
+
+    // Verified copy is unpacked, even if the original changes afterwards
+    std::fs::write(dir.path().join("etcd.tar.gz"), b"tampered").unwrap();
+    assert_eq!(_contents(&payload), b"etcd");
+    assert!(_payload(&request).is_err());
+
+    // Manifest replaced along with the archive is refused as well
+    std::fs::write(dir.path().join(MANIFEST_NAME), _manifest_toml("3.4.16")).unwrap();
+    let error = manifest(&source).unwrap_err();
+    assert!(error.msg.contains("does not match artifacts.manifest_sha256"));
+
+    let invalid_name = "[[archive]]\nname = \"../etcd.tar.gz\"\nversion = \"3.4.15\"\nsha256 = \"\"\n";
+    std::fs::write(dir.path().join(MANIFEST_NAME), invalid_name).unwrap();
+    let source = ArtifactSource::Directory {
+      path: dir.path().display().to_string(),
+      manifest_sha256: _sha256(invalid_name)
+    };
+    let error = manifest(&source).unwrap_err();
+    assert!(error.msg.contains("invalid name '../etcd.tar.gz'"));
+  }
+
+  #[test]
+  fn test_archives_are_read_from_bundle() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("bundle.tar.gz");
+    let manifest_toml = _manifest_toml("3.4.15");
+    let mut builder = Builder::new(
+      flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::fast())
+    );
+    for (name, contents) in &[(MANIFEST_NAME, manifest_toml.as_bytes()), ("etcd.tar.gz", &b"etcd"[..])] {
+      let mut header = Header::new_gnu();
+      header.set_size(contents.len() as u64);
+      header.set_mode(0o644);
+      header.set_cksum();
+      builder.append_data(&mut header, name, *contents).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+
+    let source = ArtifactSource::Bundle {
+      path: path.display().to_string(),
+      manifest_sha256: _sha256(&manifest_toml)
+    };
+    let request = ArtifactRequest { source: &source, name: "etcd", version: None };
+    let (entry, payload) = _payload(&request).unwrap();
+    assert_eq!(entry.version, "3.4.15");
+    assert_eq!(_contents(&payload), b"etcd");
+  }
+
+  fn _archive(
+    entries: &[(&str, EntryType, u32)]
+  ) -> Vec<u8> {
+    let mut builder = Builder::new(vec![]);
+    for (path, entry_type, mode) in entries {
+      let mut header = Header::new_gnu();
+      // Bypasses sanitization of `set_path`
+      header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+      header.set_entry_type(*entry_type);
+      header.set_mode(*mode);
+      header.set_size(path.len() as u64);
+      header.set_cksum();
+      builder.append(&header, path.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap()
+  }
+
+  fn _extract(
+    archive: Vec<u8>,
+    destination: &Path,
+    file_names: &[&str]
+  ) -> Result<(), InstallError> {
+    _extract_files(
+      "etcd.tar.gz",
+      &mut Archive::new(Cursor::new(archive)),
+      destination,
+      &file_names.iter().map(OsString::from).collect()
+    )
+  }
+
+  #[test]
+  fn test_only_whitelisted_files_are_extracted() {
+    let dir = TempDir::new().unwrap();
+    let archive = _archive(&[
+      ("etcd-v3.4.14/README.md", EntryType::Regular, 0o644),
+      ("etcd-v3.4.14/etcd", EntryType::Regular, 0o755),
+      ("etcd-v3.4.14/Documentation/etcdctl.md", EntryType::Regular, 0o644),
+      ("etcd-v3.4.14/etcdctl", EntryType::Regular, 0o4755)
+    ]);
+    _extract(archive, dir.path(), &["etcd", "etcdctl"]).unwrap();
+
+    let mut files = std::fs::read_dir(dir.path()).unwrap()
+      .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+      .collect::<Vec<String>>();
+    files.sort();
+    assert_eq!(files, vec!["etcd", "etcdctl"]);
+    assert_eq!(std::fs::read_to_string(dir.path().join("etcd")).unwrap(), "etcd-v3.4.14/etcd");
+    // Setuid bit is dropped
+    let metadata = std::fs::metadata(dir.path().join("etcdctl")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+  }
+
+  #[test]
+  fn test_every_compression_is_streamed() {
+    let tar = _archive(&[("etcd-v3.4.14/etcd", EntryType::Regular, 0o755)]);
+    let compressed = vec![
+      ("etcd.tar.gz", {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+        io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+      }),
+      ("etcd.tar.xz", {
+        let mut encoder = xz2::write::XzEncoder::new(vec![], 1);
+        io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+      }),
+      ("etcd.tar.zst", zstd::stream::encode_all(&tar[..], 1).unwrap())
+    ];
+    for (name, bytes) in compressed {
+      let dir = TempDir::new().unwrap();
+      let decoder = Compression::from_name(name).unwrap().decoder(&bytes[..]).unwrap();
+      let file_names = vec![OsString::from("etcd")].into_iter().collect();
+      _extract_files(name, &mut Archive::new(decoder), dir.path(), &file_names).unwrap();
+      assert_eq!(std::fs::read_to_string(dir.path().join("etcd")).unwrap(), "etcd-v3.4.14/etcd");
+    }
+
+    let error = Compression::from_name("etcd.tar.bz2").unwrap_err();
+    assert!(error.msg.contains("neither .tar.gz, .tar.xz nor .tar.zst"));
+  }
+
+  #[test]
+  fn test_unsafe_entries_are_refused() {
+    let dir = TempDir::new().unwrap();
+    let archive = _archive(&[("../etcd", EntryType::Regular, 0o755)]);
+    let error = _extract(archive, dir.path(), &["etcd"]).unwrap_err();
+    assert!(error.msg.contains("unsafe path"));
+
+    let archive = _archive(&[("etcd-v3.4.14/etcd", EntryType::Symlink, 0o777)]);
+    let error = _extract(archive, dir.path(), &["etcd"]).unwrap_err();
+    assert!(error.msg.contains("is a link"));
+
+    let archive = _archive(&[("etcd-v3.4.14/etcd", EntryType::Regular, 0o755)]);
+    let error = _extract(archive, dir.path(), &["etcd", "etcdctl"]).unwrap_err();
+    assert!(error.msg.contains("does not contain etcdctl"));
   }
 }
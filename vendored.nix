@@ -3,28 +3,31 @@
 let
   sources = import ./nix/sources.nix;
   pkgs = import sources.nixpkgs {};
+  containerd_version = "1.4.3";
   containerd_pkg = pkgs.fetchurl {
     name = "containerd";
-    url = "https://github.com/containerd/containerd/releases/download/v1.4.3/cri-containerd-cni-1.4.3-linux-amd64.tar.gz";
+    url = "https://github.com/containerd/containerd/releases/download/v${containerd_version}/cri-containerd-cni-${containerd_version}-linux-amd64.tar.gz";
     sha256 = "0ggz5fl517smd87346gdblgssckyzmcy44w3nhd22z27wd1a75r6";
   };
+  etcd_version = "3.4.14";
   etcd_pkg = pkgs.fetchurl {
     name = "etcd";
-    url = "https://github.com/etcd-io/etcd/releases/download/v3.4.14/etcd-v3.4.14-linux-amd64.tar.gz";
+    url = "https://github.com/etcd-io/etcd/releases/download/v${etcd_version}/etcd-v${etcd_version}-linux-amd64.tar.gz";
     sha256 = "0l8nc1hhhw81v68bw2chnp92msili6wx1g7hwa27lwpbbka42wgm";
   };
 in
 pkgs.stdenv.mkDerivation({
   name = "rusty-sailor-vendored-packages";
   src = ./Cargo.lock;
+  nativeBuildInputs = [ pkgs.zstd ];
   phases = [ "installPhase" ];
   installPhase = ''
     mkdir -p $out
     # Etcd binaries
     cp ${etcd_pkg} "$out/${etcd_pkg.name}.tar.gz"
 
-    # Containerd binaries with
-    # unfortunate repacking
+    # Containerd binaries with unfortunate
+    # repacking, which is done with zstd
     cp ${containerd_pkg} ./cni-containerd.tar.gz
     mkdir -p cni-containerd-unpacked
     mkdir -p containerd
@@ -35,7 +38,18 @@ pkgs.stdenv.mkDerivation({
     cp ./cni-containerd-unpacked/opt/cni/bin/* ./containerd
 
     cd ./containerd
-    tar -czvf $out/${containerd_pkg.name}.tar.gz .
+    tar -cvf - . | zstd -19 -o $out/${containerd_pkg.name}.tar.zst
     cd ..
+
+    # Manifest, that build.rs embeds and the
+    # installer verifies archives against
+    for archive in "${etcd_pkg.name}.tar.gz:${etcd_version}" "${containerd_pkg.name}.tar.zst:${containerd_version}"; do
+      name="''${archive%%:*}"
+      echo "[[archive]]" >> $out/manifest.toml
+      echo "name = \"$name\"" >> $out/manifest.toml
+      echo "version = \"''${archive#*:}\"" >> $out/manifest.toml
+      echo "sha256 = \"$(sha256sum "$out/$name" | cut -d ' ' -f 1)\"" >> $out/manifest.toml
+      echo >> $out/manifest.toml
+    done
   '';
 })